@@ -1,11 +1,21 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::file_panel::FilePanel;
+use crate::editor::{EditSession, SaveOutcome};
+use crate::file_panel::{FilePanel, NameFilter, SortKey};
 use crate::filesystem::{FileEntry, LocalFileSystem};
+use crate::hotlist::{Bookmark, Hotlist, Location, RemoteTarget};
+use crate::input::{InputHistory, TextInput};
+use crate::jobs::{JobKind, TransferJob, TransferQueue};
+use crate::keys;
+use crate::scrollback::ScrollbackView;
 use crate::shell::{LocalShell, RemoteShell, ShellType};
 use crate::ssh::{RemoteFileSystem, SshConnection};
-use crate::transfer;
+use crate::transfer::{self, CopyMode, Endpoint, TransferItem};
+use crate::viewer::{parse_offset, ViewMode, Viewer};
+use crate::vt::{Screen, DEFAULT_SCROLLBACK};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActivePanel {
@@ -15,52 +25,143 @@ pub enum ActivePanel {
 
 #[derive(Debug, Clone)]
 pub enum ConfirmationAction {
-    Copy { source: FileEntry, dest_path: PathBuf },
-    Move { source: FileEntry, dest_path: PathBuf },
-    Delete { entry: FileEntry },
+    /// `existing` counts the sources already at the destination, which are replaced
+    Copy { sources: Vec<FileEntry>, dest_dir: PathBuf, existing: usize },
+    Move { sources: Vec<FileEntry>, dest_dir: PathBuf, existing: usize },
+    Delete { entries: Vec<FileEntry> },
+    /// The destination is a shorter copy of the source, likely an interrupted transfer
+    Resume {
+        kind: JobKind,
+        source: FileEntry,
+        dest_path: PathBuf,
+        existing: u64,
+    },
+    /// An edited remote file was also changed on the server meanwhile
+    UploadEdited { name: String },
+    /// A bookmark on another host replaces the open connection and its shell
+    SwitchHost { bookmark: Bookmark, current: String },
+}
+
+/// What the text typed into the input dialog is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    Mark,
+    Unmark,
+    MakeDirectory,
+    Rename,
+    ViewerSearch,
+    ViewerGotoLine,
+    ViewerGotoOffset,
+    TerminalSearch,
+    Filter,
+    AddBookmark,
+    GoToPath,
+}
+
+impl InputAction {
+    pub fn title(&self) -> &'static str {
+        match self {
+            InputAction::Mark => "Select files",
+            InputAction::Unmark => "Unselect files",
+            InputAction::MakeDirectory => "Make directory",
+            InputAction::Rename => "Rename/move to",
+            InputAction::ViewerSearch => "Search",
+            InputAction::ViewerGotoLine => "Go to line",
+            InputAction::ViewerGotoOffset => "Go to offset",
+            InputAction::TerminalSearch => "Search terminal history",
+            InputAction::Filter => "Filter (glob or /regex/, empty for all)",
+            InputAction::AddBookmark => "Bookmark name",
+            InputAction::GoToPath => "Go to path (Tab completes)",
+        }
+    }
+}
+
+pub struct InputDialog {
+    pub action: InputAction,
+    pub input: TextInput,
+    /// Why the current text was not accepted
+    pub error: Option<String>,
 }
 
 pub struct App {
     pub left_panel: FilePanel,
     pub right_panel: FilePanel,
     pub active_panel: ActivePanel,
-    pub remote_connection: Option<String>,
+    pub remote_connection: Option<RemoteTarget>,
     pub show_help: bool,
     pub show_terminal: bool,
+    /// The terminal covers the whole screen and the panels are hidden
+    pub terminal_fullscreen: bool,
     pub terminal_input_mode: bool,
+    /// The last key was an Esc handed to a full-screen program in the shell
+    escape_forwarded: bool,
     pub confirmation_dialog: Option<ConfirmationAction>,
+    pub input_dialog: Option<InputDialog>,
+    /// Selected row of the recent directories popup while it is open
+    pub recent_popup: Option<usize>,
+    pub hotlist: Hotlist,
+    /// Selected row of the hotlist popup while it is open
+    pub hotlist_popup: Option<usize>,
+    /// A bookmark on a host to connect to; the main loop leaves the TUI for
+    /// the password and host key prompts
+    pub pending_connection: Option<Bookmark>,
+    input_history: HashMap<InputAction, InputHistory>,
+    pub viewer: Option<Viewer>,
+    /// A file ready to be opened in the external editor by the main loop
+    pub pending_edit: Option<EditSession>,
+    /// An edit waiting for the user to resolve an upload conflict
+    conflicted_edit: Option<EditSession>,
     pub status_message: Option<String>,
     pub visible_rows: usize,
     pub left_shell: Option<ShellType>,
     pub right_shell: Option<ShellType>,
+    /// Set while the terminal pane shows history instead of the live screen
+    pub terminal_scroll: Option<ScrollbackView>,
+    /// Text last copied from the terminal, pasted with Shift-Insert
+    pub clipboard: Option<String>,
+    /// Copied text the main loop should also hand to the system clipboard
+    pub copied_text: Option<String>,
+    pub transfers: TransferQueue,
+    pub show_transfer_progress: bool,
+    /// Lines of history kept by each shell, including ones started later
+    scrollback_limit: usize,
 }
 
 impl App {
-    pub fn new(remote_connection: Option<String>, ssh_connection: Option<SshConnection>) -> Result<Self> {
+    pub fn new(remote_connection: Option<RemoteTarget>, ssh_connection: Option<SshConnection>) -> Result<Self> {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
         
         let left_panel = FilePanel::new(LocalFileSystem::new(), home.clone())?;
         
         // Initialize local shell for left panel
-        let left_shell = LocalShell::new().ok().map(ShellType::Local);
+        let left_shell = LocalShell::new(DEFAULT_SCROLLBACK).ok().map(ShellType::Local);
         
         // If SSH connection provided, use remote filesystem for right panel
+        let mut shell_error = None;
         let (right_panel, right_shell) = if let Some(ref ssh_conn) = ssh_connection {
-            let remote_fs = RemoteFileSystem::new(ssh_conn);
-            let sftp_handle = remote_fs.sftp_handle();
-            let remote_home = ssh_conn.home_dir.clone();
-            let panel = FilePanel::new_remote(remote_fs, remote_home, sftp_handle)?;
-            
-            // Create remote shell using the SSH session
-            let remote_shell = RemoteShell::new(&ssh_conn.session).ok().map(ShellType::Remote);
-            
-            (panel, remote_shell)
+            let (panel, shell) = remote_panel(ssh_conn, DEFAULT_SCROLLBACK)?;
+            match shell {
+                Ok(shell) => (panel, Some(shell)),
+                Err(e) => {
+                    shell_error = Some(e);
+                    (panel, None)
+                }
+            }
         } else {
             let panel = FilePanel::new(LocalFileSystem::new(), home)?;
-            let local_shell = LocalShell::new().ok().map(ShellType::Local);
+            let local_shell = LocalShell::new(DEFAULT_SCROLLBACK).ok().map(ShellType::Local);
             (panel, local_shell)
         };
 
+        // A hotlist that cannot be read should not keep the app from starting
+        let (hotlist, status_message) = match Hotlist::load() {
+            Ok(hotlist) => (hotlist, None),
+            Err(e) => (Hotlist::default(), Some(format!("Hotlist not loaded: {:#}", e))),
+        };
+        let status_message = shell_error
+            .map(|e| format!("Remote shell unavailable: {:#}", e))
+            .or(status_message);
+
         Ok(Self {
             left_panel,
             right_panel,
@@ -68,12 +169,29 @@ impl App {
             remote_connection,
             show_help: false,
             show_terminal: false,
+            terminal_fullscreen: false,
             terminal_input_mode: false,
+            escape_forwarded: false,
             confirmation_dialog: None,
-            status_message: None,
+            input_dialog: None,
+            recent_popup: None,
+            hotlist,
+            hotlist_popup: None,
+            pending_connection: None,
+            input_history: HashMap::new(),
+            viewer: None,
+            pending_edit: None,
+            conflicted_edit: None,
+            status_message,
             visible_rows: 20, // Will be updated by UI
             left_shell,
             right_shell,
+            terminal_scroll: None,
+            clipboard: None,
+            copied_text: None,
+            transfers: TransferQueue::new(),
+            show_transfer_progress: false,
+            scrollback_limit: DEFAULT_SCROLLBACK,
         })
     }
 
@@ -98,7 +216,17 @@ impl App {
         }
     }
 
+    /// The active panel as transfer source and the inactive one as destination
+    fn source_and_dest_panels(&self) -> (&FilePanel, &FilePanel) {
+        match self.active_panel {
+            ActivePanel::Left => (&self.left_panel, &self.right_panel),
+            ActivePanel::Right => (&self.right_panel, &self.left_panel),
+        }
+    }
+
     pub fn toggle_active_panel(&mut self) {
+        // The history being browsed belongs to the other panel's shell
+        self.terminal_scroll = None;
         self.active_panel = match self.active_panel {
             ActivePanel::Left => ActivePanel::Right,
             ActivePanel::Right => ActivePanel::Left,
@@ -166,12 +294,241 @@ impl App {
         Ok(())
     }
 
+    /// Return to the previous directory in the active panel's history
+    pub fn go_back(&mut self) -> Result<()> {
+        let result = self.active_panel_mut().go_back();
+        self.report_history_step(result, "No earlier directory");
+        Ok(())
+    }
+
+    /// Go forward again after `go_back`
+    pub fn go_forward(&mut self) -> Result<()> {
+        let result = self.active_panel_mut().go_forward();
+        self.report_history_step(result, "No later directory");
+        Ok(())
+    }
+
+    fn report_history_step(&mut self, result: Result<Option<usize>>, empty: &str) {
+        self.status_message = match result {
+            Ok(Some(0)) => return,
+            Ok(Some(skipped)) => Some(format!(
+                "Skipped directories that no longer open ({})",
+                skipped
+            )),
+            Ok(None) => Some(empty.to_string()),
+            Err(e) => Some(format!("{:#}", e)),
+        };
+    }
+
+    /// Open the recent directories popup on the directory before this one
+    pub fn show_recent_directories(&mut self) {
+        let count = self.active_panel().recent_directories().len();
+        self.recent_popup = Some(if count > 1 { 1 } else { 0 });
+    }
+
+    pub fn move_recent_selection(&mut self, down: bool) {
+        let count = self.active_panel().recent_directories().len();
+        if let Some(selected) = self.recent_popup.as_mut() {
+            *selected = if down {
+                (*selected + 1).min(count.saturating_sub(1))
+            } else {
+                selected.saturating_sub(1)
+            };
+        }
+    }
+
+    /// Change the active panel to the directory picked in the popup
+    pub fn open_recent_directory(&mut self) -> Result<()> {
+        let Some(selected) = self.recent_popup.take() else {
+            return Ok(());
+        };
+        let panel = self.active_panel_mut();
+        if let Some(path) = panel.recent_directories().get(selected).cloned() {
+            panel.change_directory(&path)?;
+            if panel.current_path != path {
+                self.status_message = Some(format!("Cannot open {}", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn close_recent_directories(&mut self) {
+        self.recent_popup = None;
+    }
+
+    /// Open the hotlist popup
+    pub fn show_hotlist(&mut self) {
+        self.hotlist_popup = Some(0);
+    }
+
+    pub fn close_hotlist(&mut self) {
+        self.hotlist_popup = None;
+    }
+
+    pub fn move_hotlist_selection(&mut self, down: bool) {
+        let count = self.hotlist.bookmarks().len();
+        if let Some(selected) = self.hotlist_popup.as_mut() {
+            *selected = if down {
+                (*selected + 1).min(count.saturating_sub(1))
+            } else {
+                selected.saturating_sub(1)
+            };
+        }
+    }
+
+    /// Ask for a name to bookmark the active panel's directory under
+    pub fn prompt_add_bookmark(&mut self) {
+        let panel = self.active_panel();
+        let name = panel
+            .current_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| panel.current_path.display().to_string());
+        self.open_input(InputAction::AddBookmark, &name);
+    }
+
+    /// Drop the selected bookmark from the hotlist
+    pub fn remove_bookmark(&mut self) {
+        let Some(selected) = self.hotlist_popup else {
+            return;
+        };
+        if let Some(bookmark) = self.hotlist.remove(selected) {
+            self.status_message = Some(match self.hotlist.save() {
+                Ok(()) => format!("Removed {} from the hotlist", bookmark.name),
+                Err(e) => format!("Hotlist not saved: {:#}", e),
+            });
+            self.move_hotlist_selection(false);
+            self.move_hotlist_selection(true);
+        }
+    }
+
+    /// Go to the selected bookmark in the right panel. Local bookmarks use
+    /// the left panel while the right one is remote, and a bookmark on a
+    /// host that is not open is left to the main loop to connect to first.
+    pub fn open_bookmark(&mut self) -> Result<()> {
+        let Some(selected) = self.hotlist_popup else {
+            return Ok(());
+        };
+        let Some(bookmark) = self.hotlist.bookmarks().get(selected).cloned() else {
+            return Ok(());
+        };
+        self.hotlist_popup = None;
+
+        let side = match &bookmark.location {
+            Location::Local if self.right_panel.is_remote() => ActivePanel::Left,
+            Location::Local => ActivePanel::Right,
+            Location::Remote(target) if self.remote_connection.as_ref() == Some(target) => {
+                ActivePanel::Right
+            }
+            Location::Remote(_) => {
+                self.confirmation_dialog = Some(match &self.remote_connection {
+                    Some(current) => ConfirmationAction::SwitchHost {
+                        bookmark,
+                        current: current.host.clone(),
+                    },
+                    None => {
+                        self.pending_connection = Some(bookmark);
+                        return Ok(());
+                    }
+                });
+                return Ok(());
+            }
+        };
+        self.go_to_bookmark(side, &bookmark)
+    }
+
+    /// Show the right panel on the host just connected to for `bookmark`
+    pub fn finish_connection(&mut self, bookmark: Bookmark, connection: Result<SshConnection>) -> Result<()> {
+        let Location::Remote(target) = &bookmark.location else {
+            return Ok(());
+        };
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(e) => {
+                self.status_message = Some(format!("Cannot connect to {}: {:#}", target.host, e));
+                return Ok(());
+            }
+        };
+
+        let (panel, shell) = remote_panel(&connection, self.scrollback_limit)?;
+        self.right_panel = panel;
+        let previous = self.remote_connection.replace(target.clone());
+        self.go_to_bookmark(ActivePanel::Right, &bookmark)?;
+        if let (Some(previous), Some(status)) = (previous, self.status_message.as_mut()) {
+            status.push_str(&format!(" (disconnected from {})", previous.host));
+        }
+        self.right_shell = match shell {
+            Ok(shell) => Some(shell),
+            Err(e) => {
+                self.status_message = Some(format!("Remote shell unavailable: {:#}", e));
+                None
+            }
+        };
+        Ok(())
+    }
+
+    fn go_to_bookmark(&mut self, side: ActivePanel, bookmark: &Bookmark) -> Result<()> {
+        self.active_panel = side;
+        let panel = self.active_panel_mut();
+        panel.change_directory(&bookmark.path)?;
+        self.status_message = Some(if panel.current_path == bookmark.path {
+            format!("{}: {}", bookmark.name, bookmark.path.display())
+        } else {
+            format!("Cannot open {}", bookmark.path.display())
+        });
+        Ok(())
+    }
+
+    /// Sort the active panel by `key`, or reverse it if already sorted so
+    pub fn sort_by(&mut self, key: SortKey) {
+        let panel = self.active_panel_mut();
+        let mut order = panel.sort_order();
+        if order.key == key {
+            order.reverse = !order.reverse;
+        } else {
+            order.key = key;
+            order.reverse = false;
+        }
+        self.status_message = Some(match panel.set_sort_order(order) {
+            Ok(()) => format!("Sorted by {}", order.label()),
+            Err(e) => format!("Cannot list {}: {:#}", panel.current_path.display(), e),
+        });
+    }
+
+    /// Show or hide dotfiles in the active panel
+    pub fn toggle_hidden_files(&mut self) -> Result<()> {
+        let panel = self.active_panel_mut();
+        panel.toggle_hidden()?;
+        let shown = panel.show_hidden();
+        self.status_message = Some(
+            if shown { "Showing hidden files" } else { "Hiding hidden files" }.to_string(),
+        );
+        Ok(())
+    }
+
+    /// Switch between directories on top and directories mixed with files
+    pub fn toggle_dirs_first(&mut self) {
+        let panel = self.active_panel_mut();
+        let mut order = panel.sort_order();
+        order.dirs_first = !order.dirs_first;
+        if let Err(e) = panel.set_sort_order(order) {
+            self.status_message = Some(format!("Cannot list {}: {:#}", panel.current_path.display(), e));
+            return;
+        }
+        self.status_message = Some(if order.dirs_first {
+            "Directories first".to_string()
+        } else {
+            "Directories mixed with files".to_string()
+        });
+    }
+
     pub fn show_help(&mut self) {
         self.show_help = !self.show_help;
     }
 
     pub fn toggle_terminal(&mut self) {
         self.show_terminal = !self.show_terminal;
+        self.terminal_scroll = None;
         // Don't clear output - let the user see the shell prompt
         self.status_message = Some(format!(
             "Terminal: {}",
@@ -179,24 +536,54 @@ impl App {
         ));
     }
 
-    pub fn clear_terminal_output(&mut self) {
-        let shell = match self.active_panel {
-            ActivePanel::Left => &mut self.left_shell,
-            ActivePanel::Right => &mut self.right_shell,
-        };
-        
-        if let Some(shell) = shell {
-            shell.clear_output();
+    /// Whether a terminal pane is on screen, half or full size
+    pub fn terminal_visible(&self) -> bool {
+        self.show_terminal || self.terminal_fullscreen
+    }
+
+    /// Hide the panels and give the shell the whole screen, or bring the
+    /// panels back
+    pub fn toggle_fullscreen_terminal(&mut self) {
+        self.terminal_scroll = None;
+        if self.terminal_fullscreen {
+            self.terminal_fullscreen = false;
+            self.terminal_input_mode = self.terminal_input_mode && self.show_terminal;
+            self.status_message = None;
+        } else {
+            self.terminal_fullscreen = true;
+            self.terminal_input_mode = true;
+            self.status_message = Some("Full-screen terminal (Ctrl-O or Esc to return)".to_string());
+        }
+    }
+
+    /// Give both shells the size of the terminal pane. Only changes reach
+    /// the PTY or SSH channel.
+    pub fn resize_shells(&mut self, rows: u16, cols: u16) {
+        if rows == 0 || cols == 0 {
+            return;
+        }
+        for shell in [self.left_shell.as_mut(), self.right_shell.as_mut()].into_iter().flatten() {
+            let size = shell.screen().lock().map(|screen| screen.size());
+            if size.is_ok_and(|size| size != (rows as usize, cols as usize)) {
+                // A failed resize keeps the old size, which still works
+                let _ = shell.resize(rows, cols);
+            }
         }
     }
 
     pub fn enter_terminal_input_mode(&mut self) {
         self.terminal_input_mode = true;
         // Don't send clear command - just enter input mode
-        self.status_message = Some("Terminal input mode (Esc to exit)".to_string());
+        self.status_message = Some(
+            "Terminal input mode (Esc to exit, Esc Esc in full-screen programs)".to_string(),
+        );
     }
 
     pub fn exit_terminal_input_mode(&mut self) {
+        if self.terminal_fullscreen {
+            self.toggle_fullscreen_terminal();
+            return;
+        }
         self.terminal_input_mode = false;
         self.status_message = Some("Navigation mode (Esc to enter terminal)".to_string());
     }
@@ -213,108 +600,695 @@ impl App {
         Ok(())
     }
 
-    pub fn view_file(&mut self) -> Result<()> {
-        self.status_message = Some("View: Not yet implemented".to_string());
+    /// The shell of the active panel
+    fn active_shell(&self) -> Option<&ShellType> {
+        match self.active_panel {
+            ActivePanel::Left => self.left_shell.as_ref(),
+            ActivePanel::Right => self.right_shell.as_ref(),
+        }
+    }
+
+    /// A mode of the active shell's screen; false without a shell
+    fn active_screen_mode(&self, mode: impl Fn(&Screen) -> bool) -> bool {
+        self.active_shell()
+            .and_then(|shell| shell.screen().lock().ok().map(|screen| mode(&screen)))
+            .unwrap_or(false)
+    }
+
+    /// Send a key press to the shell, encoded as an xterm would
+    pub fn send_key_to_shell(&mut self, key: KeyEvent) -> Result<()> {
+        self.escape_forwarded = false;
+        let application_cursor = self.active_screen_mode(Screen::application_cursor);
+        match keys::encode_key(key, application_cursor) {
+            Some(bytes) => self.send_to_shell(&bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Send pasted text to the shell, bracketed if the program asked for it
+    pub fn paste_to_shell(&mut self, text: &str) -> Result<()> {
+        self.escape_forwarded = false;
+        let bracketed = self.active_screen_mode(Screen::bracketed_paste);
+        self.send_to_shell(&keys::encode_paste(text, bracketed))
+    }
+
+    /// Esc leaves terminal input mode. A full-screen program (on the
+    /// alternate screen) gets the Esc instead, and a second Esc in a row
+    /// leaves.
+    pub fn terminal_escape(&mut self) -> Result<()> {
+        let full_screen_program = self.active_screen_mode(Screen::alternate_screen);
+        if full_screen_program && !self.escape_forwarded {
+            self.escape_forwarded = true;
+            return self.send_to_shell(b"\x1b");
+        }
+        self.escape_forwarded = false;
+        self.exit_terminal_input_mode();
         Ok(())
     }
 
-    pub fn edit_file(&mut self) -> Result<()> {
-        self.status_message = Some("Edit: Not yet implemented".to_string());
+    /// Send the text last copied from the terminal to the shell
+    pub fn paste_clipboard(&mut self) -> Result<()> {
+        match self.clipboard.clone() {
+            Some(text) => self.paste_to_shell(&text),
+            None => {
+                self.status_message = Some("Clipboard is empty".to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// How many lines each shell keeps once they scroll off the terminal
+    pub fn set_scrollback_limit(&mut self, limit: usize) {
+        self.scrollback_limit = limit;
+        for shell in [&self.left_shell, &self.right_shell].into_iter().flatten() {
+            if let Ok(mut screen) = shell.screen().lock() {
+                screen.set_scrollback_limit(limit);
+            }
+        }
+    }
+
+    /// Run `f` on the scrollback view together with the screen it browses
+    pub fn with_scrollback<R>(&mut self, f: impl FnOnce(&mut ScrollbackView, &Screen) -> R) -> Option<R> {
+        let shell = match self.active_panel {
+            ActivePanel::Left => self.left_shell.as_ref(),
+            ActivePanel::Right => self.right_shell.as_ref(),
+        };
+        let screen = shell?.screen().lock().ok()?;
+        let view = self.terminal_scroll.as_mut()?;
+        Some(f(view, &screen))
+    }
+
+    /// Page back through the terminal history, entering scrollback mode first
+    pub fn scroll_terminal_back(&mut self) {
+        if self.terminal_scroll.is_none() {
+            let shell = match self.active_panel {
+                ActivePanel::Left => self.left_shell.as_ref(),
+                ActivePanel::Right => self.right_shell.as_ref(),
+            };
+            let Some(screen) = shell.and_then(|shell| shell.screen().lock().ok()) else {
+                return;
+            };
+            self.terminal_scroll = Some(ScrollbackView::new(&screen, screen.rows().len()));
+            self.status_message = Some(
+                "Scrollback: ↑↓ PgUp/PgDn move, / search, Space select, Enter copy, Esc exit"
+                    .to_string(),
+            );
+        }
+        self.with_scrollback(|view, screen| view.page_up(screen));
+    }
+
+    /// Page towards the live screen, leaving scrollback mode on reaching it
+    pub fn scroll_terminal_forward(&mut self) {
+        if self.with_scrollback(|view, screen| {
+            view.page_down(screen);
+            view.at_bottom(screen)
+        }) == Some(true)
+        {
+            self.exit_scrollback();
+        }
+    }
+
+    pub fn exit_scrollback(&mut self) {
+        self.terminal_scroll = None;
+        self.status_message = None;
+    }
+
+    /// Copy the selected lines, or the cursor line, and leave scrollback mode
+    pub fn copy_terminal_selection(&mut self) {
+        let Some(text) = self.with_scrollback(|view, screen| view.selected_text(screen)) else {
+            return;
+        };
+        self.terminal_scroll = None;
+        self.status_message = Some(format!("Copied {} lines", text.lines().count().max(1)));
+        self.clipboard = Some(text.clone());
+        self.copied_text = Some(text);
+    }
+
+    pub fn prompt_terminal_search(&mut self) {
+        if let Some(view) = self.terminal_scroll.as_ref() {
+            let initial = view.last_search().unwrap_or("").to_string();
+            self.open_input(InputAction::TerminalSearch, &initial);
+        }
+    }
+
+    /// Repeat the last terminal search, towards older lines unless `backward` is false
+    pub fn search_terminal_next(&mut self, backward: bool) {
+        let text = self
+            .terminal_scroll
+            .as_ref()
+            .and_then(|view| view.last_search())
+            .map(str::to_string);
+        match text {
+            Some(text) => self.search_terminal(&text, backward),
+            None => self.prompt_terminal_search(),
+        }
+    }
+
+    fn search_terminal(&mut self, text: &str, backward: bool) {
+        let found = self.with_scrollback(|view, screen| view.search(text, backward, screen));
+        self.status_message = match found {
+            Some(false) => Some(format!("Not found: {}", text)),
+            _ => None,
+        };
+    }
+
+    pub fn view_file(&mut self) -> Result<()> {
+        let entry = match self.active_panel().selected_entry() {
+            Some(entry) => entry.clone(),
+            None => {
+                self.status_message = Some("No file selected".to_string());
+                return Ok(());
+            }
+        };
+        if entry.is_dir {
+            self.status_message = Some("Cannot view a directory".to_string());
+            return Ok(());
+        }
+
+        match Viewer::open(&Endpoint::of(self.active_panel()), &entry.path) {
+            Ok(viewer) => self.viewer = Some(viewer),
+            Err(e) => self.status_message = Some(format!("View failed: {:#}", e)),
+        }
         Ok(())
     }
 
-    pub fn copy_file(&mut self) -> Result<()> {
-        // Get source entry from active panel
-        let source_entry = match self.active_panel().selected_entry() {
+    pub fn close_viewer(&mut self) {
+        self.viewer = None;
+    }
+
+    /// Ask for text to search for in the viewer
+    pub fn prompt_viewer_search(&mut self) {
+        if let Some(viewer) = &self.viewer {
+            let initial = viewer.last_search().unwrap_or("").to_string();
+            self.open_input(InputAction::ViewerSearch, &initial);
+        }
+    }
+
+    /// Ask for a line number, or a byte offset in hex mode
+    pub fn prompt_viewer_goto(&mut self) {
+        if let Some(viewer) = &self.viewer {
+            let action = match viewer.mode {
+                ViewMode::Text => InputAction::ViewerGotoLine,
+                ViewMode::Hex => InputAction::ViewerGotoOffset,
+            };
+            self.open_input(action, "");
+        }
+    }
+
+    pub fn edit_file(&mut self) -> Result<()> {
+        let entry = match self.active_panel().selected_entry() {
             Some(entry) => entry.clone(),
             None => {
                 self.status_message = Some("No file selected".to_string());
                 return Ok(());
             }
         };
+        if entry.is_dir {
+            self.status_message = Some("Cannot edit a directory".to_string());
+            return Ok(());
+        }
+
+        // Remote files are downloaded here; the main loop runs the editor
+        match EditSession::start(&Endpoint::of(self.active_panel()), &entry.path) {
+            Ok(session) => self.pending_edit = Some(session),
+            Err(e) => self.status_message = Some(format!("Edit failed: {:#}", e)),
+        }
+        Ok(())
+    }
+
+    /// Save the result of an editor run back to where the file came from
+    pub fn finish_edit(&mut self, session: EditSession, editor_result: Result<()>) -> Result<()> {
+        if let Err(e) = editor_result {
+            self.status_message = Some(format!("Edit failed: {:#}", e));
+            session.discard();
+            return Ok(());
+        }
+
+        self.save_edit(session, false)
+    }
+
+    fn save_edit(&mut self, session: EditSession, force: bool) -> Result<()> {
+        let name = session.name();
+        match session.save(force) {
+            Ok(SaveOutcome::Unchanged) => {
+                self.status_message = Some(format!("{} unchanged", name));
+                session.discard();
+            }
+            Ok(SaveOutcome::Saved) => {
+                self.status_message = Some(format!("Saved {}", name));
+                session.discard();
+                self.left_panel.refresh()?;
+                self.right_panel.refresh()?;
+            }
+            Ok(SaveOutcome::Conflict) => {
+                self.conflicted_edit = Some(session);
+                self.confirmation_dialog = Some(ConfirmationAction::UploadEdited { name });
+            }
+            Err(e) => {
+                // Keep the edited copy so the work is not lost
+                self.status_message = Some(format!(
+                    "Upload failed: {:#}; edited copy kept at {}",
+                    e,
+                    session.local_path().display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn copy_file(&mut self) -> Result<()> {
+        // Get source entries from active panel
+        let sources = self.active_panel().operation_targets();
+        if sources.is_empty() {
+            self.status_message = Some("No file selected".to_string());
+            return Ok(());
+        }
 
         // Skip ".." entry
-        if source_entry.name == ".." {
+        if sources.iter().any(|e| e.name == "..") {
             self.status_message = Some("Cannot copy parent directory reference".to_string());
             return Ok(());
         }
 
-        // Directories not yet supported
-        if source_entry.is_dir {
-            self.status_message = Some("Directory copy not yet implemented".to_string());
+        // Destination is the inactive panel's current directory
+        let dest_dir = self.inactive_panel().current_path.clone();
+
+        if let Some(action) = self.resume_offer(JobKind::Copy, &sources, &dest_dir) {
+            self.confirmation_dialog = Some(action);
             return Ok(());
         }
 
-        // Get destination path (inactive panel's current directory + filename)
-        let dest_path = self.inactive_panel().current_path.join(&source_entry.name);
-
         // Show confirmation dialog
-        self.confirmation_dialog = Some(ConfirmationAction::Copy {
-            source: source_entry,
-            dest_path,
-        });
+        let existing = self.existing_destinations(&sources, &dest_dir);
+        self.confirmation_dialog = Some(ConfirmationAction::Copy { sources, dest_dir, existing });
 
         Ok(())
     }
 
     pub fn move_file(&mut self) -> Result<()> {
-        // Get source entry from active panel
-        let source_entry = match self.active_panel().selected_entry() {
-            Some(entry) => entry.clone(),
-            None => {
-                self.status_message = Some("No file selected".to_string());
-                return Ok(());
-            }
-        };
+        // Get source entries from active panel
+        let sources = self.active_panel().operation_targets();
+        if sources.is_empty() {
+            self.status_message = Some("No file selected".to_string());
+            return Ok(());
+        }
 
         // Skip ".." entry
-        if source_entry.name == ".." {
+        if sources.iter().any(|e| e.name == "..") {
             self.status_message = Some("Cannot move parent directory reference".to_string());
             return Ok(());
         }
 
-        // Directories not yet supported
-        if source_entry.is_dir {
-            self.status_message = Some("Directory move not yet implemented".to_string());
+        // Get destination directory
+        let dest_dir = self.inactive_panel().current_path.clone();
+
+        if let Some(action) = self.resume_offer(JobKind::Move, &sources, &dest_dir) {
+            self.confirmation_dialog = Some(action);
             return Ok(());
         }
 
-        // Get destination path
-        let dest_path = self.inactive_panel().current_path.join(&source_entry.name);
-
         // Show confirmation dialog
-        self.confirmation_dialog = Some(ConfirmationAction::Move {
-            source: source_entry,
-            dest_path,
-        });
+        let existing = self.existing_destinations(&sources, &dest_dir);
+        self.confirmation_dialog = Some(ConfirmationAction::Move { sources, dest_dir, existing });
 
         Ok(())
     }
 
+    /// How many of `sources` already have an entry of the same name in `dest_dir`
+    fn existing_destinations(&self, sources: &[FileEntry], dest_dir: &Path) -> usize {
+        let panel = self.inactive_panel();
+        sources
+            .iter()
+            .filter(|source| panel.exists(&dest_dir.join(&source.name)))
+            .count()
+    }
+
+    /// A resume dialog when a single file is being transferred and a shorter
+    /// file of the same name is already at the destination
+    fn resume_offer(
+        &self,
+        kind: JobKind,
+        sources: &[FileEntry],
+        dest_dir: &Path,
+    ) -> Option<ConfirmationAction> {
+        let [source] = sources else {
+            return None;
+        };
+        if source.is_dir {
+            return None;
+        }
+
+        let dest_path = dest_dir.join(&source.name);
+        let existing = transfer::file_size(&Endpoint::of(self.inactive_panel()), &dest_path)?;
+        (existing > 0 && existing < source.size).then(|| ConfirmationAction::Resume {
+            kind,
+            source: source.clone(),
+            dest_path,
+            existing,
+        })
+    }
+
     pub fn make_directory(&mut self) -> Result<()> {
-        self.status_message = Some("MkDir: Not yet implemented (needs input dialog)".to_string());
+        self.open_input(InputAction::MakeDirectory, "");
         Ok(())
     }
 
-    pub fn delete_file(&mut self) -> Result<()> {
-        // Get selected entry from active panel
-        let entry = match self.active_panel().selected_entry() {
-            Some(entry) => entry.clone(),
+    /// Ask for a new name or destination path for the selected entry
+    pub fn rename_file(&mut self) -> Result<()> {
+        let name = match self.active_panel().selected_entry() {
+            Some(entry) if entry.name != ".." => entry.name.clone(),
+            Some(_) => {
+                self.status_message = Some("Cannot rename parent directory reference".to_string());
+                return Ok(());
+            }
             None => {
                 self.status_message = Some("No file selected".to_string());
                 return Ok(());
             }
         };
+        self.open_input(InputAction::Rename, &name);
+        Ok(())
+    }
+
+    pub fn delete_file(&mut self) -> Result<()> {
+        // Get target entries from active panel
+        let entries = self.active_panel().operation_targets();
+        if entries.is_empty() {
+            self.status_message = Some("No file selected".to_string());
+            return Ok(());
+        }
 
         // Skip ".." entry
-        if entry.name == ".." {
+        if entries.iter().any(|e| e.name == "..") {
             self.status_message = Some("Cannot delete parent directory reference".to_string());
             return Ok(());
         }
 
         // Show confirmation dialog
-        self.confirmation_dialog = Some(ConfirmationAction::Delete { entry });
+        self.confirmation_dialog = Some(ConfirmationAction::Delete { entries });
+
+        Ok(())
+    }
+
+    pub fn toggle_mark(&mut self) {
+        self.active_panel_mut().toggle_mark();
+    }
+
+    pub fn invert_marks(&mut self) {
+        self.active_panel_mut().invert_marks();
+    }
+
+    /// Ask for a glob pattern to mark (`+`) or unmark (`-`) entries by
+    pub fn prompt_mark_pattern(&mut self, mark: bool) {
+        let action = if mark {
+            InputAction::Mark
+        } else {
+            InputAction::Unmark
+        };
+        self.open_input(action, "*");
+    }
+
+    /// Ask for the active panel's name filter, starting from the current one
+    pub fn prompt_filter(&mut self) {
+        let current = self
+            .active_panel()
+            .filter()
+            .map(|filter| filter.text().to_string())
+            .unwrap_or_default();
+        self.open_input(InputAction::Filter, &current);
+    }
+
+    /// Ask for a path to go to, starting from the active panel's directory
+    pub fn prompt_go_to_path(&mut self) {
+        let mut current = self.active_panel().current_path.display().to_string();
+        if !current.ends_with('/') {
+            current.push('/');
+        }
+        self.open_input(InputAction::GoToPath, &current);
+    }
+
+    /// Tab in the go-to-path prompt: complete the typed path on the active
+    /// panel's filesystem, listing the candidates when they differ
+    pub fn complete_input_path(&mut self) {
+        let Some(text) = self.input_dialog.as_ref().map(|dialog| dialog.input.text().to_string()) else {
+            return;
+        };
+        let (completed, names) = self.active_panel().complete_path(&text);
+        if names.len() > 1 && completed == text {
+            self.status_message = Some(names.join("  "));
+        }
+        if let Some(dialog) = self.input_dialog.as_mut() {
+            dialog.error = None;
+            dialog.input.set_text(&completed);
+        }
+    }
+
+    /// Keys typed while the active panel's quick search is open: characters
+    /// extend it, Backspace shortens it and Esc closes it. Any other key
+    /// closes it and is left for normal handling, as is everything when no
+    /// quick search is open. Returns whether the key was used.
+    pub fn handle_quick_search_key(&mut self, key: KeyEvent) -> bool {
+        let panel = self.active_panel_mut();
+        if panel.quick_search().is_none() {
+            return false;
+        }
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                panel.quick_search_push(c);
+            }
+            KeyCode::Backspace => panel.quick_search_pop(),
+            KeyCode::Esc => panel.end_quick_search(),
+            _ => {
+                panel.end_quick_search();
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Show the input dialog, with earlier entries for the same action on Up/Down
+    fn open_input(&mut self, action: InputAction, initial: &str) {
+        let history = self.input_history.entry(action).or_default();
+        self.input_dialog = Some(InputDialog {
+            action,
+            input: TextInput::new(initial).with_history(history),
+            error: None,
+        });
+    }
+
+    pub fn cancel_input(&mut self) {
+        self.input_dialog = None;
+    }
+
+    /// Apply the text typed into the input dialog. Text that does not
+    /// validate keeps the dialog open with an error.
+    pub fn submit_input(&mut self) -> Result<()> {
+        let (action, text) = match &self.input_dialog {
+            Some(dialog) => (dialog.action, dialog.input.text().trim().to_string()),
+            None => return Ok(()),
+        };
+
+        if let Err(message) = self.validate_input(action, &text) {
+            if let Some(dialog) = self.input_dialog.as_mut() {
+                dialog.error = Some(message);
+            }
+            return Ok(());
+        }
+        self.input_dialog = None;
+        if !text.is_empty() {
+            self.input_history.entry(action).or_default().push(&text);
+        }
+
+        match action {
+            InputAction::Mark | InputAction::Unmark => {
+                let mark = action == InputAction::Mark;
+                let matched = self.active_panel_mut().mark_matching(&text, mark);
+                self.status_message = Some(format!(
+                    "{} {} entries matching {}",
+                    if mark { "Marked" } else { "Unmarked" },
+                    matched,
+                    text
+                ));
+            }
+            InputAction::MakeDirectory => {
+                let path = self.active_panel().current_path.join(&text);
+                match transfer::create_directory(&Endpoint::of(self.active_panel()), &path) {
+                    Ok(()) => {
+                        self.status_message = Some(format!("Created {}", text));
+                        let panel = self.active_panel_mut();
+                        panel.refresh()?;
+                        panel.select_name(&text);
+                    }
+                    Err(e) => self.status_message = Some(format!("MkDir failed: {:#}", e)),
+                }
+            }
+            InputAction::Rename => self.rename_selected(&text)?,
+            InputAction::ViewerSearch => {
+                if let Some(viewer) = self.viewer.as_mut() {
+                    viewer.search(&text);
+                }
+            }
+            InputAction::ViewerGotoLine | InputAction::ViewerGotoOffset => {
+                if let Some(viewer) = self.viewer.as_mut() {
+                    viewer.goto(&text);
+                }
+            }
+            InputAction::TerminalSearch => self.search_terminal(&text, true),
+            InputAction::Filter => {
+                let filter = if text.is_empty() {
+                    None
+                } else {
+                    NameFilter::parse(&text).ok()
+                };
+                self.status_message = Some(match &filter {
+                    Some(filter) => format!("Filter: {}", filter.text()),
+                    None => "Filter cleared".to_string(),
+                });
+                self.active_panel_mut().set_filter(filter)?;
+            }
+            InputAction::AddBookmark => {
+                let panel = self.active_panel();
+                let location = match &self.remote_connection {
+                    Some(target) if panel.is_remote() => Location::Remote(target.clone()),
+                    _ => Location::Local,
+                };
+                self.hotlist.add(Bookmark {
+                    name: text.clone(),
+                    location,
+                    path: panel.current_path.clone(),
+                });
+                self.status_message = Some(match self.hotlist.save() {
+                    Ok(()) => format!("Added {} to the hotlist", text),
+                    Err(e) => format!("Hotlist not saved: {:#}", e),
+                });
+                if self.hotlist_popup.is_some() {
+                    self.hotlist_popup = Some(self.hotlist.bookmarks().len() - 1);
+                }
+            }
+            InputAction::GoToPath => {
+                let panel = self.active_panel_mut();
+                let path = panel.resolve_path(&text);
+                if !panel.go_to_path(&path)? {
+                    self.status_message = Some(format!("Cannot open {}", path.display()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check input before acting on it, returning a message to show in the dialog
+    fn validate_input(&self, action: InputAction, text: &str) -> std::result::Result<(), String> {
+        if text.is_empty() && action != InputAction::Filter {
+            return Err("Please enter a value".to_string());
+        }
+
+        match action {
+            InputAction::Filter if !text.is_empty() => {
+                NameFilter::parse(text)?;
+            }
+            InputAction::MakeDirectory => {
+                if text == "." || text == ".." {
+                    return Err("Not a valid directory name".to_string());
+                }
+                let panel = self.active_panel();
+                if panel.exists(&panel.current_path.join(text)) {
+                    return Err(format!("'{}' already exists", text));
+                }
+            }
+            InputAction::Rename => {
+                let (source, dest) = self.rename_paths(text).ok_or("No file selected")?;
+                if dest == source {
+                    return Err("Enter a different name or path".to_string());
+                }
+                if self.active_panel().exists(&dest) {
+                    return Err(format!("'{}' already exists", dest.display()));
+                }
+            }
+            InputAction::AddBookmark => {
+                if text.contains('\t') {
+                    return Err("Names cannot contain tabs".to_string());
+                }
+                if self.hotlist.contains_name(text) {
+                    return Err(format!("'{}' is already in the hotlist", text));
+                }
+            }
+            InputAction::GoToPath => {
+                let path = self.active_panel().resolve_path(text);
+                if !self.active_panel().exists(&path) {
+                    return Err(format!("'{}' does not exist", path.display()));
+                }
+            }
+            InputAction::ViewerGotoLine if text.parse::<u64>().is_err() => {
+                return Err("Not a line number".to_string());
+            }
+            InputAction::ViewerGotoOffset if parse_offset(text).is_err() => {
+                return Err("Not an offset (decimal or 0x...)".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Source and destination of renaming the selected entry to `text`: a
+    /// new name, or a path relative to the panel directory or absolute.
+    /// An existing directory as the destination means "move into it".
+    fn rename_paths(&self, text: &str) -> Option<(PathBuf, PathBuf)> {
+        let panel = self.active_panel();
+        let entry = panel.selected_entry().filter(|e| e.name != "..")?;
 
+        let mut dest = panel.current_path.join(text);
+        if dest != entry.path && panel.is_directory(&dest) {
+            dest = dest.join(&entry.name);
+        }
+        Some((entry.path.clone(), dest))
+    }
+
+    /// Rename in place, or queue a move when the destination is on another filesystem
+    fn rename_selected(&mut self, text: &str) -> Result<()> {
+        let (source, dest) = match self.rename_paths(text) {
+            Some(paths) => paths,
+            None => return Ok(()),
+        };
+        let endpoint = Endpoint::of(self.active_panel());
+        let name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        match transfer::rename_path(&endpoint, &endpoint, &source, &dest) {
+            Ok(true) => {
+                self.status_message = Some(format!("Renamed {} to {}", name, dest.display()));
+                let panel = self.active_panel_mut();
+                panel.refresh()?;
+                if let Some(new_name) = dest.file_name() {
+                    panel.select_name(&new_name.to_string_lossy());
+                }
+                // The other panel may show either directory
+                match self.active_panel {
+                    ActivePanel::Left => self.right_panel.refresh()?,
+                    ActivePanel::Right => self.left_panel.refresh()?,
+                }
+            }
+            Ok(false) => {
+                let is_dir = self.active_panel().is_directory(&source);
+                let job = TransferJob {
+                    kind: JobKind::Move,
+                    name: name.clone(),
+                    source: endpoint.clone(),
+                    dest: endpoint,
+                    items: vec![TransferItem {
+                        source_path: source,
+                        dest_path: dest,
+                        is_dir,
+                    }],
+                    mode: CopyMode::Overwrite,
+                };
+                self.transfers.submit(job);
+                self.show_transfer_progress = true;
+                self.status_message = Some(format!("{} {}...", JobKind::Move.verb(), name));
+            }
+            Err(e) => self.status_message = Some(format!("Rename failed: {:#}", e)),
+        }
         Ok(())
     }
 
@@ -327,119 +1301,215 @@ impl App {
     pub fn confirm_action(&mut self) -> Result<()> {
         if let Some(action) = self.confirmation_dialog.take() {
             match action {
-                ConfirmationAction::Copy { source, dest_path } => {
-                    // Perform copy based on active panel
-                    let result = match self.active_panel {
-                        ActivePanel::Left => {
-                            transfer::copy_file(&self.left_panel, &self.right_panel, &source.path, &dest_path)
-                        }
-                        ActivePanel::Right => {
-                            transfer::copy_file(&self.right_panel, &self.left_panel, &source.path, &dest_path)
-                        }
-                    };
-
-                    match result {
-                        Ok(bytes) => {
-                            self.status_message = Some(format!(
-                                "Copied {} ({} bytes)",
-                                source.name,
-                                bytes
-                            ));
-                            // Refresh destination panel
-                            match self.active_panel {
-                                ActivePanel::Left => self.right_panel.refresh()?,
-                                ActivePanel::Right => self.left_panel.refresh()?,
-                            }
-                        }
-                        Err(e) => {
-                            self.status_message = Some(format!("Copy failed: {}", e));
-                        }
-                    }
+                ConfirmationAction::Copy { sources, dest_dir, .. } => {
+                    self.submit_transfer(JobKind::Copy, sources, &dest_dir, CopyMode::Overwrite);
                 }
-                ConfirmationAction::Move { source, dest_path } => {
-                    // Perform copy then delete (move = copy + delete source)
-                    let copy_result = match self.active_panel {
-                        ActivePanel::Left => {
-                            transfer::copy_file(&self.left_panel, &self.right_panel, &source.path, &dest_path)
-                        }
-                        ActivePanel::Right => {
-                            transfer::copy_file(&self.right_panel, &self.left_panel, &source.path, &dest_path)
-                        }
-                    };
-
-                    match copy_result {
-                        Ok(bytes) => {
-                            // Delete source file
-                            let delete_result = match self.active_panel {
-                                ActivePanel::Left => transfer::delete_file(&self.left_panel, &source.path),
-                                ActivePanel::Right => transfer::delete_file(&self.right_panel, &source.path),
-                            };
-
-                            match delete_result {
-                                Ok(()) => {
-                                    self.status_message = Some(format!(
-                                        "Moved {} ({} bytes)",
-                                        source.name,
-                                        bytes
-                                    ));
-                                    // Refresh both panels
-                                    self.left_panel.refresh()?;
-                                    self.right_panel.refresh()?;
-                                }
-                                Err(e) => {
-                                    self.status_message = Some(format!(
-                                        "Copied but failed to delete source: {}",
-                                        e
-                                    ));
-                                    // Still refresh destination
-                                    match self.active_panel {
-                                        ActivePanel::Left => self.right_panel.refresh()?,
-                                        ActivePanel::Right => self.left_panel.refresh()?,
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            self.status_message = Some(format!("Move failed: {}", e));
-                        }
+                ConfirmationAction::Move { sources, dest_dir, .. } => {
+                    self.submit_transfer(JobKind::Move, sources, &dest_dir, CopyMode::Overwrite);
+                }
+                ConfirmationAction::Resume { kind, source, dest_path, .. } => {
+                    // Confirming without choosing resume starts over
+                    let dest_dir = dest_path.parent().unwrap_or(&dest_path).to_path_buf();
+                    self.submit_transfer(kind, vec![source], &dest_dir, CopyMode::Overwrite);
+                }
+                ConfirmationAction::UploadEdited { .. } => {
+                    if let Some(session) = self.conflicted_edit.take() {
+                        self.save_edit(session, true)?;
                     }
                 }
-                ConfirmationAction::Delete { entry } => {
-                    // Delete based on type
-                    let result = if entry.is_dir {
-                        match self.active_panel {
-                            ActivePanel::Left => transfer::delete_directory(&self.left_panel, &entry.path),
-                            ActivePanel::Right => transfer::delete_directory(&self.right_panel, &entry.path),
-                        }
-                    } else {
-                        match self.active_panel {
-                            ActivePanel::Left => transfer::delete_file(&self.left_panel, &entry.path),
-                            ActivePanel::Right => transfer::delete_file(&self.right_panel, &entry.path),
+                ConfirmationAction::SwitchHost { bookmark, .. } => {
+                    self.pending_connection = Some(bookmark);
+                }
+                ConfirmationAction::Delete { entries } => {
+                    // Delete based on type, stopping at the first failure
+                    let endpoint = Endpoint::of(self.active_panel());
+                    let result = entries.iter().try_for_each(|entry| {
+                        if entry.is_dir {
+                            transfer::delete_directory(&endpoint, &entry.path)
+                        } else {
+                            transfer::delete_file(&endpoint, &entry.path)
                         }
-                    };
+                    });
 
                     match result {
                         Ok(()) => {
-                            self.status_message = Some(format!("Deleted {}", entry.name));
-                            // Refresh active panel
-                            self.active_panel_mut().refresh()?;
+                            self.status_message = Some(match entries.as_slice() {
+                                [entry] => format!("Deleted {}", entry.name),
+                                _ => format!("Deleted {} items", entries.len()),
+                            });
                         }
                         Err(e) => {
-                            self.status_message = Some(format!("Delete failed: {}", e));
+                            self.status_message = Some(format!("Delete failed: {:#}", e));
                         }
                     }
+                    // Refresh active panel
+                    self.active_panel_mut().refresh()?;
                 }
             }
         }
         Ok(())
     }
 
+    /// Continue a partial destination offered by a resume dialog
+    pub fn confirm_resume(&mut self, verify: bool) {
+        if !matches!(self.confirmation_dialog, Some(ConfirmationAction::Resume { .. })) {
+            return;
+        }
+        if let Some(ConfirmationAction::Resume { kind, source, dest_path, .. }) =
+            self.confirmation_dialog.take()
+        {
+            let mode = if verify {
+                CopyMode::ResumeVerified
+            } else {
+                CopyMode::Resume
+            };
+            let dest_dir = dest_path.parent().unwrap_or(&dest_path).to_path_buf();
+            self.submit_transfer(kind, vec![source], &dest_dir, mode);
+        }
+    }
+
+    /// Queue a copy or move from the active panel to the inactive one
+    fn submit_transfer(
+        &mut self,
+        kind: JobKind,
+        sources: Vec<FileEntry>,
+        dest_dir: &Path,
+        mode: CopyMode,
+    ) {
+        let name = match sources.as_slice() {
+            [source] => source.name.clone(),
+            _ => format!("{} items", sources.len()),
+        };
+        let items = sources
+            .into_iter()
+            .map(|source| TransferItem {
+                dest_path: dest_dir.join(&source.name),
+                source_path: source.path,
+                is_dir: source.is_dir,
+            })
+            .collect();
+
+        let (source_panel, dest_panel) = self.source_and_dest_panels();
+        let job = TransferJob {
+            kind,
+            name: name.clone(),
+            source: Endpoint::of(source_panel),
+            dest: Endpoint::of(dest_panel),
+            items,
+            mode,
+        };
+
+        self.transfers.submit(job);
+        self.show_transfer_progress = true;
+        // The marked set has been handed over to the job
+        self.active_panel_mut().clear_marks();
+        self.status_message = Some(format!("{} {}...", kind.verb(), name));
+    }
+
+    /// Pick up finished background transfers and refresh the panels they touched
+    pub fn poll_transfers(&mut self) -> Result<()> {
+        let finished = self.transfers.poll();
+        if finished.is_empty() {
+            return Ok(());
+        }
+
+        for outcome in &finished {
+            self.status_message = Some(outcome.status_message());
+        }
+        if !self.transfers.is_busy() {
+            self.show_transfer_progress = false;
+        }
+
+        // Refresh both panels; a job may have removed the directory one shows
+        for panel in [&mut self.left_panel, &mut self.right_panel] {
+            let path = panel.current_path.clone();
+            if let Err(e) = panel.refresh_or_parent() {
+                self.status_message = Some(format!("Cannot refresh {}: {:#}", path.display(), e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep each shell in its panel's directory, and move the panel when the
+    /// shell changes directory itself
+    pub fn sync_shell_directories(&mut self) {
+        let sides = [
+            (self.left_shell.as_mut(), &mut self.left_panel),
+            (self.right_shell.as_mut(), &mut self.right_panel),
+        ];
+        let mut failed = None;
+        for (shell, panel) in sides {
+            let Some(shell) = shell else {
+                continue;
+            };
+            // A shell that stopped accepting input just stays where it is
+            if let Ok(Some(dir)) = shell.sync_directory(&panel.current_path) {
+                if let Err(e) = panel.change_directory(&dir) {
+                    failed = Some(format!("Cannot follow shell to {}: {:#}", dir.display(), e));
+                }
+            }
+        }
+        if failed.is_some() {
+            self.status_message = failed;
+        }
+    }
+
+    /// Whether the modal progress dialog should be drawn and receive keys
+    pub fn transfer_dialog_visible(&self) -> bool {
+        self.show_transfer_progress && self.transfers.is_busy()
+    }
+
+    pub fn cancel_transfer(&mut self) {
+        self.transfers.cancel_active();
+        self.status_message = Some("Cancelling transfer...".to_string());
+    }
+
+    pub fn show_transfers(&mut self) {
+        if self.transfers.is_busy() {
+            self.show_transfer_progress = true;
+        } else {
+            self.status_message = Some("No transfers running".to_string());
+        }
+    }
+
+    pub fn background_transfer(&mut self) {
+        self.show_transfer_progress = false;
+        self.status_message = Some("Transfer continues in background".to_string());
+    }
+
     pub fn cancel_confirmation(&mut self) {
         self.confirmation_dialog = None;
         self.status_message = Some("Cancelled".to_string());
+
+        // Not uploading a conflicting edit must not throw it away
+        if let Some(session) = self.conflicted_edit.take() {
+            self.status_message = Some(format!(
+                "Not uploaded; edited copy kept at {}",
+                session.local_path().display()
+            ));
+        }
     }
 }
 
+/// A panel on the host of `connection`, and its shell or why there is none
+fn remote_panel(
+    connection: &SshConnection,
+    scrollback_limit: usize,
+) -> Result<(FilePanel, Result<ShellType>)> {
+    let remote_fs = RemoteFileSystem::new(connection);
+    let sftp_handle = remote_fs.sftp_handle();
+    let remote_home = connection.home_dir.clone();
+    let panel = FilePanel::new_remote(remote_fs, remote_home, sftp_handle)?;
+
+    // Create remote shell on its own SSH session
+    let remote_shell = match &connection.shell_session {
+        Ok(session) => RemoteShell::new(session.clone(), scrollback_limit).map(ShellType::Remote),
+        Err(e) => Err(anyhow!("{}", e)),
+    };
+
+    Ok((panel, remote_shell))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -453,8 +1523,8 @@ mod tests {
 
     #[test]
     fn test_app_with_remote_string() {
-        let app = App::new(Some("user@host".to_string()), None).unwrap();
-        assert_eq!(app.remote_connection, Some("user@host".to_string()));
+        let app = App::new(Some(RemoteTarget::new("user@host")), None).unwrap();
+        assert_eq!(app.remote_connection, Some(RemoteTarget::new("user@host")));
     }
 
     #[test]
@@ -467,6 +1537,85 @@ mod tests {
         assert_eq!(app.active_panel, ActivePanel::Left);
     }
 
+    #[test]
+    fn test_fullscreen_terminal_restores_panels() {
+        let mut app = App::new(None, None).unwrap();
+        app.toggle_fullscreen_terminal();
+        assert!(app.terminal_visible());
+        assert!(app.terminal_input_mode);
+
+        // Esc leaves the full-screen shell rather than the input mode alone
+        app.exit_terminal_input_mode();
+        assert!(!app.terminal_fullscreen);
+        assert!(!app.terminal_visible());
+        assert!(!app.terminal_input_mode);
+    }
+
+    #[test]
+    fn test_open_local_bookmark() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut app = App::new(None, None).unwrap();
+        app.hotlist = Hotlist::default();
+        app.hotlist.add(Bookmark {
+            name: "tmp".to_string(),
+            location: Location::Local,
+            path: temp_dir.path().to_path_buf(),
+        });
+
+        app.show_hotlist();
+        app.open_bookmark().unwrap();
+        assert!(app.hotlist_popup.is_none());
+        assert!(app.pending_connection.is_none());
+        assert_eq!(app.active_panel, ActivePanel::Right);
+        assert_eq!(app.right_panel.current_path, temp_dir.path());
+    }
+
+    #[test]
+    fn test_remote_bookmark_waits_for_connection() {
+        let mut app = App::new(None, None).unwrap();
+        app.hotlist = Hotlist::default();
+        app.hotlist.add(Bookmark {
+            name: "www".to_string(),
+            location: Location::Remote(RemoteTarget::new("web1")),
+            path: PathBuf::from("/var/www"),
+        });
+
+        app.show_hotlist();
+        app.open_bookmark().unwrap();
+        assert_eq!(app.pending_connection.unwrap().name, "www");
+        assert_eq!(app.active_panel, ActivePanel::Left);
+    }
+
+    #[test]
+    fn test_bookmark_on_another_host_asks_first() {
+        let mut app = App::new(Some(RemoteTarget::new("web0")), None).unwrap();
+        app.hotlist = Hotlist::default();
+        app.hotlist.add(Bookmark {
+            name: "www".to_string(),
+            location: Location::Remote(RemoteTarget::new("web1")),
+            path: PathBuf::from("/var/www"),
+        });
+
+        app.show_hotlist();
+        app.open_bookmark().unwrap();
+        assert!(app.pending_connection.is_none());
+        assert!(matches!(
+            &app.confirmation_dialog,
+            Some(ConfirmationAction::SwitchHost { current, .. }) if current == "web0"
+        ));
+        app.confirm_action().unwrap();
+        assert_eq!(app.pending_connection.unwrap().name, "www");
+    }
+
+    #[test]
+    fn test_resize_shells() {
+        let mut app = App::new(None, None).unwrap();
+        app.resize_shells(10, 40);
+        for shell in [&app.left_shell, &app.right_shell].into_iter().flatten() {
+            assert_eq!(shell.screen().lock().unwrap().size(), (10, 40));
+        }
+    }
+
     #[test]
     fn test_navigation() {
         let mut app = App::new(None, None).unwrap();
@@ -494,4 +1643,121 @@ mod tests {
             app.active_panel().entries.len().saturating_sub(1)
         );
     }
+
+    #[test]
+    fn test_copy_offers_resume_for_partial_destination() {
+        let source_dir = tempfile::TempDir::new().unwrap();
+        let dest_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(source_dir.path().join("big.bin"), "full contents").unwrap();
+        std::fs::write(dest_dir.path().join("big.bin"), "full").unwrap();
+
+        let mut app = App::new(None, None).unwrap();
+        app.left_panel = FilePanel::new(LocalFileSystem::new(), source_dir.path().to_path_buf()).unwrap();
+        app.right_panel = FilePanel::new(LocalFileSystem::new(), dest_dir.path().to_path_buf()).unwrap();
+        app.left_panel.selected_index = app
+            .left_panel
+            .entries
+            .iter()
+            .position(|e| e.name == "big.bin")
+            .unwrap();
+
+        app.copy_file().unwrap();
+
+        assert!(matches!(
+            app.confirmation_dialog,
+            Some(ConfirmationAction::Resume { existing: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_copy_uses_marked_entries() {
+        let source_dir = tempfile::TempDir::new().unwrap();
+        let dest_dir = tempfile::TempDir::new().unwrap();
+        for name in ["a.log", "b.log", "c.txt"] {
+            std::fs::write(source_dir.path().join(name), name).unwrap();
+        }
+
+        let mut app = App::new(None, None).unwrap();
+        app.left_panel = FilePanel::new(LocalFileSystem::new(), source_dir.path().to_path_buf()).unwrap();
+        app.right_panel = FilePanel::new(LocalFileSystem::new(), dest_dir.path().to_path_buf()).unwrap();
+
+        app.open_input(InputAction::Mark, "*.log");
+        app.submit_input().unwrap();
+        assert_eq!(app.left_panel.marked_count(), 2);
+
+        app.copy_file().unwrap();
+
+        match &app.confirmation_dialog {
+            Some(ConfirmationAction::Copy { sources, dest_dir: dir, existing }) => {
+                let mut names: Vec<_> = sources.iter().map(|e| e.name.as_str()).collect();
+                names.sort();
+                assert_eq!(names, ["a.log", "b.log"]);
+                assert_eq!(dir, dest_dir.path());
+                assert_eq!(*existing, 0);
+            }
+            _ => panic!("expected a copy confirmation"),
+        }
+    }
+
+    #[test]
+    fn test_move_over_existing_asks_first() {
+        let source_dir = tempfile::TempDir::new().unwrap();
+        let dest_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(source_dir.path().join("notes.txt"), "new").unwrap();
+        std::fs::write(dest_dir.path().join("notes.txt"), "old").unwrap();
+
+        let mut app = App::new(None, None).unwrap();
+        app.left_panel = FilePanel::new(LocalFileSystem::new(), source_dir.path().to_path_buf()).unwrap();
+        app.right_panel = FilePanel::new(LocalFileSystem::new(), dest_dir.path().to_path_buf()).unwrap();
+        assert!(app.left_panel.select_name("notes.txt"));
+
+        app.move_file().unwrap();
+        assert!(matches!(
+            app.confirmation_dialog,
+            Some(ConfirmationAction::Move { existing: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_make_directory_validates_name() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("existing")).unwrap();
+
+        let mut app = App::new(None, None).unwrap();
+        app.left_panel = FilePanel::new(LocalFileSystem::new(), temp_dir.path().to_path_buf()).unwrap();
+
+        app.make_directory().unwrap();
+        app.input_dialog.as_mut().unwrap().input.set_text("existing");
+        app.submit_input().unwrap();
+        let dialog = app.input_dialog.as_ref().expect("dialog stays open");
+        assert!(dialog.error.is_some());
+
+        app.input_dialog.as_mut().unwrap().input.set_text("new");
+        app.submit_input().unwrap();
+        assert!(app.input_dialog.is_none());
+        assert!(temp_dir.path().join("new").is_dir());
+        assert_eq!(app.left_panel.selected_entry().unwrap().name, "new");
+    }
+
+    #[test]
+    fn test_rename_into_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "x").unwrap();
+        std::fs::create_dir(temp_dir.path().join("archive")).unwrap();
+
+        let mut app = App::new(None, None).unwrap();
+        app.left_panel = FilePanel::new(LocalFileSystem::new(), temp_dir.path().to_path_buf()).unwrap();
+        assert!(app.left_panel.select_name("notes.txt"));
+
+        app.rename_file().unwrap();
+        assert_eq!(app.input_dialog.as_ref().unwrap().input.text(), "notes.txt");
+        app.submit_input().unwrap();
+        assert!(app.input_dialog.as_ref().unwrap().error.is_some());
+
+        app.input_dialog.as_mut().unwrap().input.set_text("archive");
+        app.submit_input().unwrap();
+        assert!(app.input_dialog.is_none());
+        assert!(temp_dir.path().join("archive/notes.txt").exists());
+        assert!(!temp_dir.path().join("notes.txt").exists());
+    }
 }
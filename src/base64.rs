@@ -0,0 +1,41 @@
+//! Standard base64 encoding, for fingerprints and OSC 52 clipboard writes
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `data`, padding with `=` to a multiple of four characters if `pad`
+/// is set. OpenSSH fingerprints leave the padding out.
+pub fn encode(data: &[u8], pad: bool) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if pad {
+            for _ in chunk.len()..3 {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b"", false), "");
+        assert_eq!(encode(b"f", false), "Zg");
+        assert_eq!(encode(b"fo", false), "Zm8");
+        assert_eq!(encode(b"foo", false), "Zm9v");
+        assert_eq!(encode(b"foobar", false), "Zm9vYmFy");
+
+        assert_eq!(encode(b"", true), "");
+        assert_eq!(encode(b"f", true), "Zg==");
+        assert_eq!(encode(b"fo", true), "Zm8=");
+        assert_eq!(encode(b"foo", true), "Zm9v");
+    }
+}
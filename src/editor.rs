@@ -0,0 +1,286 @@
+//! Editing files with the user's $EDITOR
+//!
+//! Local files are edited in place. Remote files are downloaded to a private
+//! temporary directory, edited there and uploaded back only if they changed.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, DirBuilder};
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ssh2::{FileStat, OpenFlags, OpenType, Sftp};
+
+use crate::transfer::{self, lock_sftp, Endpoint};
+
+/// Distinguishes temp directories created by one process
+static EDIT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// What saving an edited file did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOutcome {
+    Unchanged,
+    Saved,
+    /// The remote file was modified by someone else since it was downloaded
+    Conflict,
+}
+
+/// A file checked out for editing
+pub struct EditSession {
+    endpoint: Endpoint,
+    path: PathBuf,
+    /// The file the editor works on: `path` itself for local files
+    local_path: PathBuf,
+    /// Private directory holding the downloaded copy of a remote file
+    temp_dir: Option<PathBuf>,
+    checksum: u64,
+    remote_mtime: Option<u64>,
+}
+
+impl EditSession {
+    /// Prepare `path` for editing, downloading it first if it is remote
+    pub fn start(endpoint: &Endpoint, path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Not a file: {}", path.display()))?;
+
+        let mut session = Self {
+            endpoint: endpoint.clone(),
+            path: path.to_path_buf(),
+            local_path: path.to_path_buf(),
+            temp_dir: None,
+            checksum: 0,
+            remote_mtime: None,
+        };
+
+        if let Endpoint::Remote(_) = endpoint {
+            let temp_dir = create_private_dir()?;
+            session.local_path = temp_dir.join(name);
+            session.temp_dir = Some(temp_dir);
+            session.remote_mtime = session.current_remote_mtime()?;
+            if let Err(e) = session.download() {
+                session.discard();
+                return Err(e);
+            }
+        }
+
+        session.checksum = checksum_file(&session.local_path)?;
+        Ok(session)
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The file to hand to the editor
+    pub fn local_path(&self) -> &Path {
+        &self.local_path
+    }
+
+    /// Write the edits back. Unless `force` is set, a remote file that
+    /// changed since it was downloaded is left alone and reported as a conflict.
+    pub fn save(&self, force: bool) -> Result<SaveOutcome> {
+        if checksum_file(&self.local_path)? == self.checksum {
+            return Ok(SaveOutcome::Unchanged);
+        }
+        if self.temp_dir.is_none() {
+            // The editor already wrote the local file in place
+            return Ok(SaveOutcome::Saved);
+        }
+
+        if !force && self.current_remote_mtime()? != self.remote_mtime {
+            return Ok(SaveOutcome::Conflict);
+        }
+        self.upload()?;
+        Ok(SaveOutcome::Saved)
+    }
+
+    /// Remove the downloaded copy, if any
+    pub fn discard(self) {
+        if let Some(temp_dir) = &self.temp_dir {
+            let _ = fs::remove_dir_all(temp_dir);
+        }
+    }
+
+    fn current_remote_mtime(&self) -> Result<Option<u64>> {
+        match &self.endpoint {
+            Endpoint::Remote(sftp) => {
+                let stat = lock_sftp(sftp)?.stat(&self.path).with_context(|| {
+                    format!("Failed to stat remote file: {}", self.path.display())
+                })?;
+                Ok(stat.mtime)
+            }
+            Endpoint::Local => Ok(None),
+        }
+    }
+
+    fn download(&self) -> Result<()> {
+        let mut source = transfer::open_for_reading(&self.endpoint, &self.path)?;
+        let mut dest = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&self.local_path)
+            .with_context(|| format!("Failed to create {}", self.local_path.display()))?;
+        std::io::copy(&mut source, &mut dest)
+            .with_context(|| format!("Failed to download {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Upload to a temporary file next to the original, give it the
+    /// original's permissions and rename it over the original, so an
+    /// interrupted upload never leaves a truncated file behind
+    fn upload(&self) -> Result<()> {
+        let Endpoint::Remote(sftp) = &self.endpoint else {
+            return Ok(());
+        };
+        let contents = fs::read(&self.local_path)
+            .with_context(|| format!("Failed to read {}", self.local_path.display()))?;
+
+        let temp_path = transfer::sibling_path(&self.path, "upload");
+        let result = self
+            .write_remote_copy(sftp, &temp_path, &contents)
+            .and_then(|()| {
+                transfer::rename_path(&self.endpoint, &self.endpoint, &temp_path, &self.path)
+            });
+        if result.is_err() {
+            if let Ok(sftp) = lock_sftp(sftp) {
+                let _ = sftp.unlink(&temp_path);
+            }
+        }
+        result.map(|_| ())
+    }
+
+    fn write_remote_copy(
+        &self,
+        sftp: &Arc<Mutex<Sftp>>,
+        temp_path: &Path,
+        contents: &[u8],
+    ) -> Result<()> {
+        let sftp = lock_sftp(sftp)?;
+        let perm = sftp
+            .stat(&self.path)
+            .with_context(|| format!("Failed to stat remote file: {}", self.path.display()))?
+            .perm;
+
+        let mut dest = sftp
+            .open_mode(
+                temp_path,
+                OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                0o600,
+                OpenType::File,
+            )
+            .with_context(|| format!("Failed to create remote file: {}", temp_path.display()))?;
+        dest.write_all(contents)
+            .with_context(|| format!("Failed to upload {}", self.path.display()))?;
+        drop(dest);
+
+        sftp.setstat(
+            temp_path,
+            FileStat {
+                size: None,
+                uid: None,
+                gid: None,
+                perm: perm.map(|perm| perm & 0o7777),
+                atime: None,
+                mtime: None,
+            },
+        )
+        .with_context(|| format!("Failed to set permissions of {}", temp_path.display()))
+    }
+}
+
+/// The editor to run: $VISUAL, then $EDITOR, then vi
+pub fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Run the editor on `path` and wait for it to exit. The command goes
+/// through the shell so settings like `EDITOR="code --wait"` work.
+pub fn run_editor(path: &Path) -> Result<()> {
+    let editor = editor_command();
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to start editor: {}", editor))?;
+
+    if !status.success() {
+        bail!("Editor {} exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// A new directory under the system temp dir that only the user can read
+fn create_private_dir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "remote-commander-{}-{}",
+        std::process::id(),
+        EDIT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+fn checksum_file(path: &Path) -> Result<u64> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buffer[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_local_edit_detects_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.conf");
+        fs::write(&path, "port = 80\n").unwrap();
+
+        let session = EditSession::start(&Endpoint::Local, &path).unwrap();
+        assert_eq!(session.local_path(), path);
+        assert_eq!(session.save(false).unwrap(), SaveOutcome::Unchanged);
+
+        fs::write(&path, "port = 8080\n").unwrap();
+        assert_eq!(session.save(false).unwrap(), SaveOutcome::Saved);
+        session.discard();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_private_dir_is_user_only() {
+        let dir = create_private_dir().unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        fs::remove_dir(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -1,14 +1,145 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ssh2::Sftp;
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use crate::filesystem::{FileEntry, FileSystem};
+use regex::Regex;
 
-/// Trait to check if a filesystem is remote and get SFTP handle
-pub trait RemoteCapable {
-    fn is_remote(&self) -> bool;
-    fn get_sftp(&self) -> Option<Arc<Mutex<Sftp>>>;
+use crate::filesystem::{glob_match, FileEntry, FileSystem};
+
+/// Directories kept in a panel's recent list
+const RECENT_LIMIT: usize = 20;
+
+/// What panel entries are ordered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Extension,
+    /// Largest first
+    Size,
+    /// Newest first
+    Modified,
+    /// As the filesystem lists them
+    Unsorted,
+}
+
+impl SortKey {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Extension => "ext",
+            SortKey::Size => "size",
+            SortKey::Modified => "time",
+            SortKey::Unsorted => "unsorted",
+        }
+    }
+}
+
+/// How a panel orders its entries; ".." always stays on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub reverse: bool,
+    pub dirs_first: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            reverse: false,
+            dirs_first: true,
+        }
+    }
+}
+
+impl SortOrder {
+    /// Sort a listing fresh from the filesystem; an unsorted order cannot be
+    /// recovered from entries sorted some other way
+    pub fn sort(&self, entries: &mut [FileEntry]) {
+        // The sort is stable, so reversing first reverses unsorted listings
+        if self.key == SortKey::Unsorted && self.reverse {
+            entries.reverse();
+        }
+        entries.sort_by(|a, b| self.compare(a, b));
+    }
+
+    /// Short description for the panel title, e.g. "size rev"
+    pub fn label(&self) -> String {
+        let mut label = self.key.label().to_string();
+        if self.reverse {
+            label.push_str(" rev");
+        }
+        if !self.dirs_first {
+            label.push_str(", mixed");
+        }
+        label
+    }
+
+    fn compare(&self, a: &FileEntry, b: &FileEntry) -> Ordering {
+        let parent = (b.name == "..").cmp(&(a.name == ".."));
+        let dirs = if self.dirs_first {
+            b.is_dir.cmp(&a.is_dir)
+        } else {
+            Ordering::Equal
+        };
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let by_key = match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Extension => extension(&a.name).cmp(&extension(&b.name)).then_with(by_name),
+            SortKey::Size => b.size.cmp(&a.size).then_with(by_name),
+            SortKey::Modified => b.modified.cmp(&a.modified).then_with(by_name),
+            SortKey::Unsorted => Ordering::Equal,
+        };
+        let by_key = if self.reverse { by_key.reverse() } else { by_key };
+
+        parent.then(dirs).then(by_key)
+    }
+}
+
+/// Lowercased extension of a file name, empty if it has none
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// A filter hiding files whose names do not match: a glob, or a regular
+/// expression written between slashes. Directories are always shown.
+#[derive(Debug, Clone)]
+pub struct NameFilter {
+    text: String,
+    regex: Option<Regex>,
+}
+
+impl NameFilter {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let regex = match text.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?),
+            None => None,
+        };
+        Ok(Self {
+            text: text.to_string(),
+            regex,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn matches(&self, entry: &FileEntry) -> bool {
+        if entry.is_dir {
+            return true;
+        }
+        match &self.regex {
+            Some(regex) => regex.is_match(&entry.name),
+            None => glob_match(&self.text, &entry.name),
+        }
+    }
 }
 
 /// Represents a file panel (left or right side)
@@ -18,23 +149,45 @@ pub struct FilePanel {
     pub selected_index: usize,
     pub scroll_offset: usize,
     pub visible_rows: usize,
+    marked: HashSet<PathBuf>,
+    sort_order: SortOrder,
+    filter: Option<NameFilter>,
+    /// Whether entries starting with a dot are listed
+    show_hidden: bool,
+    /// Text typed into the quick search box while it is open
+    quick_search: Option<String>,
+    /// Directories to go back to, most recent last
+    back: Vec<PathBuf>,
+    /// Directories left by going back, most recent last
+    forward: Vec<PathBuf>,
+    /// Directories visited, most recent first
+    recent: Vec<PathBuf>,
     filesystem: Box<dyn FileSystem>,
     sftp_handle: Option<Arc<Mutex<Sftp>>>,
 }
 
 impl FilePanel {
     pub fn new<F: FileSystem + 'static>(filesystem: F, path: PathBuf) -> Result<Self> {
-        let entries = filesystem.list_directory(&path)?;
-        
-        Ok(Self {
+        let mut panel = Self {
             current_path: path,
-            entries,
+            entries: Vec::new(),
             selected_index: 0,
             scroll_offset: 0,
             visible_rows: 20,
+            marked: HashSet::new(),
+            sort_order: SortOrder::default(),
+            filter: None,
+            show_hidden: true,
+            quick_search: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            recent: Vec::new(),
             filesystem: Box::new(filesystem),
             sftp_handle: None,
-        })
+        };
+        panel.entries = panel.list(&panel.current_path)?;
+        panel.recent.push(panel.current_path.clone());
+        Ok(panel)
     }
 
     pub fn new_remote<F: FileSystem + 'static>(
@@ -42,17 +195,26 @@ impl FilePanel {
         path: PathBuf,
         sftp: Arc<Mutex<Sftp>>,
     ) -> Result<Self> {
-        let entries = filesystem.list_directory(&path)?;
-        
-        Ok(Self {
+        let mut panel = Self {
             current_path: path,
-            entries,
+            entries: Vec::new(),
             selected_index: 0,
             scroll_offset: 0,
             visible_rows: 20,
+            marked: HashSet::new(),
+            sort_order: SortOrder::default(),
+            filter: None,
+            show_hidden: true,
+            quick_search: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            recent: Vec::new(),
             filesystem: Box::new(filesystem),
             sftp_handle: Some(sftp),
-        })
+        };
+        panel.entries = panel.list(&panel.current_path)?;
+        panel.recent.push(panel.current_path.clone());
+        Ok(panel)
     }
 
     pub fn is_remote(&self) -> bool {
@@ -63,28 +225,426 @@ impl FilePanel {
         self.sftp_handle.clone()
     }
 
+    /// The entries of `path` the panel shows, filtered and sorted
+    fn list(&self, path: &Path) -> Result<Vec<FileEntry>> {
+        let mut entries = self.filesystem.list_directory(path)?;
+        if !self.show_hidden {
+            entries.retain(|entry| entry.name == ".." || !entry.name.starts_with('.'));
+        }
+        if let Some(filter) = &self.filter {
+            entries.retain(|entry| filter.matches(entry));
+        }
+        self.sort_order.sort(&mut entries);
+        Ok(entries)
+    }
+
     pub fn refresh(&mut self) -> Result<()> {
-        self.entries = self.filesystem.list_directory(&self.current_path)?;
+        self.entries = self.list(&self.current_path)?;
+        // Forget marks on entries that no longer exist
+        let entries = &self.entries;
+        self.marked.retain(|path| entries.iter().any(|e| &e.path == path));
         if self.selected_index >= self.entries.len() {
             self.selected_index = self.entries.len().saturating_sub(1);
         }
         Ok(())
     }
 
-    pub fn change_directory(&mut self, path: &PathBuf) -> Result<()> {
-        if self.filesystem.is_directory(path) {
-            self.entries = self.filesystem.list_directory(path)?;
-            self.current_path = path.clone();
-            self.selected_index = 0;
-            self.scroll_offset = 0;
+    /// Refresh, moving up to the nearest directory that still exists when
+    /// the current one is gone. The refresh error is still returned.
+    pub fn refresh_or_parent(&mut self) -> Result<()> {
+        let Err(error) = self.refresh() else {
+            return Ok(());
+        };
+        let mut dir = self.current_path.clone();
+        while dir.pop() {
+            if self.filesystem.is_directory(&dir) {
+                self.change_directory(&dir)?;
+                break;
+            }
+        }
+        Err(error)
+    }
+
+    pub fn change_directory(&mut self, path: &Path) -> Result<()> {
+        let previous = self.current_path.clone();
+        if self.load_directory(path)? && previous != self.current_path {
+            self.back.push(previous);
+            self.forward.clear();
+        }
+        Ok(())
+    }
+
+    /// Return to the directory visited before this one. See `step_history`.
+    pub fn go_back(&mut self) -> Result<Option<usize>> {
+        self.step_history(true)
+    }
+
+    /// Undo the last `go_back`
+    pub fn go_forward(&mut self) -> Result<Option<usize>> {
+        self.step_history(false)
+    }
+
+    /// Move through the history, skipping directories that can no longer be
+    /// opened. Returns None if there is no history that way, or how many
+    /// entries were skipped. If none of them opens, the history is left as it
+    /// was and the first error is returned.
+    fn step_history(&mut self, back: bool) -> Result<Option<usize>> {
+        let mut stack = std::mem::take(if back { &mut self.back } else { &mut self.forward });
+        let previous = self.current_path.clone();
+        let mut first_error = None;
+        let mut result = Ok(None);
+
+        for index in (0..stack.len()).rev() {
+            match self.load_directory(&stack[index]) {
+                Ok(true) => {
+                    result = Ok(Some(stack.len() - 1 - index));
+                    stack.truncate(index);
+                    break;
+                }
+                Ok(false) => {
+                    first_error.get_or_insert_with(|| {
+                        anyhow!("Cannot open {}", stack[index].display())
+                    });
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if let (Ok(None), Some(e)) = (&result, first_error) {
+            result = Err(e);
+        }
+
+        if back {
+            self.back = stack;
+            if let Ok(Some(_)) = result {
+                self.forward.push(previous);
+            }
+        } else {
+            self.forward = stack;
+            if let Ok(Some(_)) = result {
+                self.back.push(previous);
+            }
+        }
+        result
+    }
+
+    /// Directories visited in this panel, most recent first
+    pub fn recent_directories(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    /// Show `path`, returning false if it is not a directory. Coming up out
+    /// of a subdirectory puts the cursor on it.
+    fn load_directory(&mut self, path: &Path) -> Result<bool> {
+        if !self.filesystem.is_directory(path) {
+            return Ok(false);
+        }
+        self.entries = self.list(path)?;
+        let previous = std::mem::replace(&mut self.current_path, path.to_path_buf());
+        self.selected_index = 0;
+        self.scroll_offset = 0;
+        self.marked.clear();
+        self.quick_search = None;
+
+        if previous.parent() == Some(path) {
+            if let Some(name) = previous.file_name() {
+                self.select_name(&name.to_string_lossy());
+            }
+        }
+
+        self.recent.retain(|recent| recent != path);
+        self.recent.insert(0, path.to_path_buf());
+        self.recent.truncate(RECENT_LIMIT);
+        Ok(true)
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        self.sort_order
+    }
+
+    /// Re-sort the listing, keeping the cursor on the same entry
+    pub fn set_sort_order(&mut self, order: SortOrder) -> Result<()> {
+        let selected = self.selected_entry().map(|e| e.name.clone());
+        let relist = self.sort_order.key == SortKey::Unsorted || order.key == SortKey::Unsorted;
+        self.sort_order = order;
+        if relist {
+            self.entries = self.list(&self.current_path)?;
+        } else {
+            self.sort_order.sort(&mut self.entries);
+        }
+        if let Some(name) = selected {
+            self.select_name(&name);
         }
         Ok(())
     }
 
+    pub fn filter(&self) -> Option<&NameFilter> {
+        self.filter.as_ref()
+    }
+
+    /// Show only files matching `filter`, or everything again with `None`.
+    /// The filter stays when changing directory.
+    pub fn set_filter(&mut self, filter: Option<NameFilter>) -> Result<()> {
+        self.filter = filter;
+        self.reload_keeping_cursor()
+    }
+
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    /// Show or hide dotfiles; the setting stays when changing directory
+    pub fn toggle_hidden(&mut self) -> Result<()> {
+        self.show_hidden = !self.show_hidden;
+        self.reload_keeping_cursor()
+    }
+
+    /// Reload the listing with the cursor on the same entry, or on the next
+    /// one still listed if it disappeared
+    fn reload_keeping_cursor(&mut self) -> Result<()> {
+        let following: Vec<String> = self
+            .entries
+            .iter()
+            .skip(self.selected_index)
+            .map(|e| e.name.clone())
+            .collect();
+        self.refresh()?;
+        for name in &following {
+            if self.select_name(name) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn quick_search(&self) -> Option<&str> {
+        self.quick_search.as_deref()
+    }
+
+    /// Add `c` to the quick search, opening it if needed, and jump to the
+    /// first entry starting with the text. A character that would match
+    /// nothing is not added.
+    pub fn quick_search_push(&mut self, c: char) -> bool {
+        let mut text = self.quick_search.take().unwrap_or_default();
+        text.push(c);
+        let found = self.jump_to_prefix(&text);
+        if !found {
+            text.pop();
+        }
+        self.quick_search = Some(text);
+        found
+    }
+
+    /// Remove the last character of the quick search
+    pub fn quick_search_pop(&mut self) {
+        if let Some(mut text) = self.quick_search.take() {
+            text.pop();
+            self.jump_to_prefix(&text);
+            self.quick_search = Some(text);
+        }
+    }
+
+    pub fn end_quick_search(&mut self) {
+        self.quick_search = None;
+    }
+
+    /// Put the cursor on the first entry whose name starts with `prefix`,
+    /// ignoring case
+    fn jump_to_prefix(&mut self, prefix: &str) -> bool {
+        let prefix = prefix.to_lowercase();
+        let found = self
+            .entries
+            .iter()
+            .position(|e| e.name != ".." && e.name.to_lowercase().starts_with(&prefix));
+        if let Some(index) = found {
+            self.selected_index = index;
+            self.adjust_scroll();
+        }
+        found.is_some()
+    }
+
+    pub fn is_directory(&self, path: &Path) -> bool {
+        self.filesystem.is_directory(path)
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.filesystem.exists(path)
+    }
+
+    /// The path typed `text` names on this panel's filesystem: absolute,
+    /// `~`-relative or relative to the current directory, with `.` and `..`
+    /// resolved
+    pub fn resolve_path(&self, text: &str) -> PathBuf {
+        let path = if text == "~" {
+            self.filesystem.home_dir()
+        } else if let Some(rest) = text.strip_prefix("~/") {
+            self.filesystem.home_dir().join(rest)
+        } else {
+            self.current_path.join(text)
+        };
+
+        let mut resolved = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                other => resolved.push(other),
+            }
+        }
+        resolved
+    }
+
+    /// Complete the last part of a typed path as far as the names in its
+    /// directory agree, adding a slash after a directory. Returns the new
+    /// text and the names that matched.
+    pub fn complete_path(&self, text: &str) -> (String, Vec<String>) {
+        if text == "~" {
+            return ("~/".to_string(), Vec::new());
+        }
+        let (dir_text, prefix) = match text.rfind('/') {
+            Some(slash) => text.split_at(slash + 1),
+            None => ("", text),
+        };
+
+        let entries = self
+            .filesystem
+            .list_directory(&self.resolve_path(dir_text))
+            .unwrap_or_default();
+        // Dotfiles only come up once a dot is typed, as in the shell
+        let mut matches: Vec<&FileEntry> = entries
+            .iter()
+            .filter(|entry| entry.name != ".." && entry.name.starts_with(prefix))
+            .filter(|entry| prefix.starts_with('.') || !entry.name.starts_with('.'))
+            .collect();
+        matches.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let completed = match matches.as_slice() {
+            [] => return (text.to_string(), Vec::new()),
+            [entry] if entry.is_dir => format!("{}/", entry.name),
+            [entry] => entry.name.clone(),
+            [first, rest @ ..] => rest.iter().fold(first.name.clone(), |common, entry| {
+                common
+                    .chars()
+                    .zip(entry.name.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a)
+                    .collect()
+            }),
+        };
+        let names = matches.iter().map(|entry| entry.name.clone()).collect();
+        (format!("{}{}", dir_text, completed), names)
+    }
+
+    /// Show `path` if it is a directory, or the directory holding it with
+    /// the cursor on it otherwise. Returns false if nothing is there.
+    pub fn go_to_path(&mut self, path: &Path) -> Result<bool> {
+        if self.filesystem.is_directory(path) {
+            self.change_directory(path)?;
+            return Ok(true);
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(false);
+        };
+        if !self.filesystem.exists(path) {
+            return Ok(false);
+        }
+        self.change_directory(parent)?;
+        self.select_name(&name.to_string_lossy());
+        Ok(true)
+    }
+
+    /// Put the cursor on the entry called `name`, if there is one
+    pub fn select_name(&mut self, name: &str) -> bool {
+        match self.entries.iter().position(|e| e.name == name) {
+            Some(index) => {
+                self.selected_index = index;
+                self.adjust_scroll();
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn selected_entry(&self) -> Option<&FileEntry> {
         self.entries.get(self.selected_index)
     }
 
+    pub fn is_marked(&self, entry: &FileEntry) -> bool {
+        self.marked.contains(&entry.path)
+    }
+
+    /// Toggle the mark on the entry under the cursor and move to the next one
+    pub fn toggle_mark(&mut self) {
+        if let Some(entry) = self.entries.get(self.selected_index) {
+            if entry.name != ".." && !self.marked.remove(&entry.path) {
+                self.marked.insert(entry.path.clone());
+            }
+        }
+        if self.selected_index + 1 < self.entries.len() {
+            self.selected_index += 1;
+            self.adjust_scroll();
+        }
+    }
+
+    /// Mark or unmark every entry whose name matches a glob pattern, returning how many matched
+    pub fn mark_matching(&mut self, pattern: &str, mark: bool) -> usize {
+        let mut matched = 0;
+        for entry in &self.entries {
+            if entry.name == ".." || !glob_match(pattern, &entry.name) {
+                continue;
+            }
+            matched += 1;
+            if mark {
+                self.marked.insert(entry.path.clone());
+            } else {
+                self.marked.remove(&entry.path);
+            }
+        }
+        matched
+    }
+
+    pub fn invert_marks(&mut self) {
+        for entry in &self.entries {
+            if entry.name != ".." && !self.marked.remove(&entry.path) {
+                self.marked.insert(entry.path.clone());
+            }
+        }
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    pub fn marked_count(&self) -> usize {
+        self.marked.len()
+    }
+
+    /// Total size of the marked files (directories count as zero)
+    pub fn marked_size(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_dir && self.is_marked(e))
+            .map(|e| e.size)
+            .sum()
+    }
+
+    /// Entries an operation applies to: the marked set in listing order, or
+    /// the entry under the cursor when nothing is marked
+    pub fn operation_targets(&self) -> Vec<FileEntry> {
+        if self.marked.is_empty() {
+            self.selected_entry().cloned().into_iter().collect()
+        } else {
+            self.entries
+                .iter()
+                .filter(|e| self.is_marked(e))
+                .cloned()
+                .collect()
+        }
+    }
+
     pub fn adjust_scroll(&mut self) {
         // Ensure selected item is visible
         if self.selected_index < self.scroll_offset {
@@ -168,6 +728,306 @@ mod tests {
         assert_eq!(panel.entries.len(), old_count + 1);
     }
 
+    #[test]
+    fn test_toggle_mark() {
+        let (_temp_dir, mut panel) = setup_test_panel();
+
+        // ".." cannot be marked, but the cursor still advances
+        panel.toggle_mark();
+        assert_eq!(panel.marked_count(), 0);
+        assert_eq!(panel.selected_index, 1);
+
+        panel.toggle_mark();
+        assert_eq!(panel.marked_count(), 1);
+        assert!(panel.is_marked(&panel.entries[1]));
+
+        panel.selected_index = 1;
+        panel.toggle_mark();
+        assert_eq!(panel.marked_count(), 0);
+    }
+
+    #[test]
+    fn test_mark_by_pattern_and_invert() {
+        let (_temp_dir, mut panel) = setup_test_panel();
+
+        assert_eq!(panel.mark_matching("*.txt", true), 2);
+        assert_eq!(panel.marked_count(), 2);
+        assert_eq!(panel.marked_size(), 16);
+
+        assert_eq!(panel.mark_matching("file1*", false), 1);
+        assert_eq!(panel.marked_count(), 1);
+
+        // dir_a, dir_b and file1.txt become marked, file2.txt unmarked
+        panel.invert_marks();
+        let names: Vec<_> = panel.operation_targets().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["dir_a", "dir_b", "file1.txt"]);
+    }
+
+    #[test]
+    fn test_operation_targets_fall_back_to_selection() {
+        let (_temp_dir, mut panel) = setup_test_panel();
+        panel.selected_index = 3;
+
+        let targets = panel.operation_targets();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].name, "file1.txt");
+    }
+
+    fn names(panel: &FilePanel) -> Vec<&str> {
+        panel.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        std::fs::write(temp_dir.path().join("big.log"), "a much longer content").unwrap();
+        std::fs::write(temp_dir.path().join("Zed"), "").unwrap();
+        panel.refresh().unwrap();
+        assert_eq!(
+            names(&panel),
+            vec!["..", "dir_a", "dir_b", "big.log", "file1.txt", "file2.txt", "Zed"]
+        );
+
+        let mut order = SortOrder { key: SortKey::Extension, ..SortOrder::default() };
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(names(&panel)[3..], ["Zed", "big.log", "file1.txt", "file2.txt"]);
+
+        order.key = SortKey::Size;
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(names(&panel)[3..], ["big.log", "file1.txt", "file2.txt", "Zed"]);
+
+        // ".." stays on top when reversed, and the cursor follows its entry
+        panel.select_name("file2.txt");
+        order = SortOrder { key: SortKey::Name, reverse: true, dirs_first: false };
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(
+            names(&panel),
+            vec!["..", "Zed", "file2.txt", "file1.txt", "dir_b", "dir_a", "big.log"]
+        );
+        assert_eq!(panel.selected_entry().unwrap().name, "file2.txt");
+
+        // The order is kept when changing directory
+        panel.change_directory(&temp_dir.path().join("dir_a")).unwrap();
+        assert_eq!(panel.sort_order(), order);
+    }
+
+    #[test]
+    fn test_unsorted_uses_directory_order() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        let listed: Vec<String> = panel
+            .filesystem
+            .list_directory(temp_dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        let mut reversed = listed.clone();
+        reversed[1..].reverse();
+
+        let mut order = SortOrder { key: SortKey::Name, reverse: false, dirs_first: false };
+        panel.set_sort_order(order).unwrap();
+        order.key = SortKey::Unsorted;
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(names(&panel), listed);
+        order.reverse = true;
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(names(&panel), reversed);
+        order.reverse = false;
+        panel.set_sort_order(order).unwrap();
+        assert_eq!(names(&panel), listed);
+    }
+
+    #[test]
+    fn test_quick_search_jumps_to_prefix() {
+        let (_temp_dir, mut panel) = setup_test_panel();
+
+        assert!(panel.quick_search_push('F'));
+        assert_eq!(panel.selected_entry().unwrap().name, "file1.txt");
+        assert!(panel.quick_search_push('i'));
+        // Nothing starts with "fix", so the x is refused
+        assert!(!panel.quick_search_push('x'));
+        assert_eq!(panel.quick_search(), Some("Fi"));
+
+        panel.quick_search_pop();
+        panel.quick_search_pop();
+        assert!(panel.quick_search_push('d'));
+        assert_eq!(panel.selected_entry().unwrap().name, "dir_a");
+
+        panel.end_quick_search();
+        assert_eq!(panel.quick_search(), None);
+    }
+
+    #[test]
+    fn test_filter_hides_files_until_cleared() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        std::fs::write(temp_dir.path().join("notes.md"), "").unwrap();
+
+        panel.set_filter(Some(NameFilter::parse("*.md").unwrap())).unwrap();
+        assert_eq!(names(&panel), vec!["..", "dir_a", "dir_b", "notes.md"]);
+
+        panel.set_filter(Some(NameFilter::parse("/^file[12]/").unwrap())).unwrap();
+        assert_eq!(names(&panel), vec!["..", "dir_a", "dir_b", "file1.txt", "file2.txt"]);
+        assert!(NameFilter::parse("/[/").is_err());
+
+        // The filter applies in other directories too
+        std::fs::write(temp_dir.path().join("dir_a").join("other.md"), "").unwrap();
+        panel.change_directory(&temp_dir.path().join("dir_a")).unwrap();
+        assert_eq!(names(&panel), vec![".."]);
+
+        panel.set_filter(None).unwrap();
+        assert_eq!(names(&panel), vec!["..", "other.md"]);
+    }
+
+    #[test]
+    fn test_hidden_files_toggle() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        std::fs::write(temp_dir.path().join(".hidden"), "").unwrap();
+        std::fs::create_dir(temp_dir.path().join("dir_a").join(".config")).unwrap();
+        panel.refresh().unwrap();
+        panel.select_name(".hidden");
+
+        // The cursor moves on to the next entry when its own is hidden
+        panel.toggle_hidden().unwrap();
+        assert!(!panel.show_hidden());
+        assert_eq!(names(&panel), vec!["..", "dir_a", "dir_b", "file1.txt", "file2.txt"]);
+        assert_eq!(panel.selected_entry().unwrap().name, "file1.txt");
+
+        panel.change_directory(&temp_dir.path().join("dir_a")).unwrap();
+        assert_eq!(names(&panel), vec![".."]);
+
+        panel.toggle_hidden().unwrap();
+        assert_eq!(names(&panel), vec!["..", ".config"]);
+        assert_eq!(panel.selected_entry().unwrap().name, "..");
+    }
+
+    #[test]
+    fn test_directory_history() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        let root = temp_dir.path().to_path_buf();
+        let dir_a = root.join("dir_a");
+        let dir_b = root.join("dir_b");
+
+        panel.change_directory(&dir_a).unwrap();
+        panel.change_directory(&root).unwrap();
+        // Coming back up lands on the directory just left
+        assert_eq!(panel.selected_entry().unwrap().name, "dir_a");
+        panel.change_directory(&dir_b).unwrap();
+
+        assert_eq!(panel.go_back().unwrap(), Some(0));
+        assert_eq!(panel.current_path, root);
+        assert_eq!(panel.selected_entry().unwrap().name, "dir_b");
+        assert_eq!(panel.go_back().unwrap(), Some(0));
+        assert_eq!(panel.current_path, dir_a);
+        assert_eq!(panel.go_forward().unwrap(), Some(0));
+        assert_eq!(panel.go_forward().unwrap(), Some(0));
+        assert_eq!(panel.current_path, dir_b);
+        assert_eq!(panel.go_forward().unwrap(), None);
+
+        // A new directory drops the forward history
+        panel.go_back().unwrap();
+        panel.change_directory(&dir_a).unwrap();
+        assert_eq!(panel.go_forward().unwrap(), None);
+
+        assert_eq!(panel.recent_directories(), &[dir_a, root, dir_b]);
+    }
+
+    #[test]
+    fn test_history_skips_removed_directories() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        let root = temp_dir.path().to_path_buf();
+        let gone = root.join("gone");
+        std::fs::create_dir(&gone).unwrap();
+        let dir_a = root.join("dir_a");
+
+        panel.change_directory(&gone).unwrap();
+        panel.change_directory(&dir_a).unwrap();
+        std::fs::remove_dir(&gone).unwrap();
+
+        // root is still there under the removed directory
+        assert_eq!(panel.go_back().unwrap(), Some(1));
+        assert_eq!(panel.current_path, root);
+        assert_eq!(panel.go_forward().unwrap(), Some(0));
+        assert_eq!(panel.current_path, dir_a);
+
+        // With nothing left to open the history stays as it was
+        panel.change_directory(&root.join("dir_b")).unwrap();
+        std::fs::remove_dir_all(&dir_a).unwrap();
+        panel.back = vec![dir_a.clone()];
+        assert!(panel.go_back().is_err());
+        assert_eq!(panel.back, vec![dir_a]);
+        assert!(panel.forward.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let (temp_dir, panel) = setup_test_panel();
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(panel.resolve_path("~"), home);
+        assert_eq!(panel.resolve_path("~/src"), home.join("src"));
+        assert_eq!(panel.resolve_path("/usr/./lib/../bin/"), PathBuf::from("/usr/bin"));
+        assert_eq!(panel.resolve_path("dir_a/.."), temp_dir.path());
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let (temp_dir, panel) = setup_test_panel();
+        std::fs::write(temp_dir.path().join(".hidden"), "").unwrap();
+        let root = format!("{}/", temp_dir.path().display());
+
+        // A single directory gets its slash, several names their common part
+        let (text, names) = panel.complete_path(&format!("{}dir_a", root));
+        assert_eq!(text, format!("{}dir_a/", root));
+        assert_eq!(names, vec!["dir_a"]);
+        let (text, names) = panel.complete_path(&format!("{}fi", root));
+        assert_eq!(text, format!("{}file", root));
+        assert_eq!(names, vec!["file1.txt", "file2.txt"]);
+
+        let (_, names) = panel.complete_path(&root);
+        assert_eq!(names, vec!["dir_a", "dir_b", "file1.txt", "file2.txt"]);
+        let (text, _) = panel.complete_path(&format!("{}.h", root));
+        assert_eq!(text, format!("{}.hidden", root));
+        let (text, names) = panel.complete_path("dir_b/x");
+        assert_eq!(text, "dir_b/x");
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn test_go_to_path() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        std::fs::write(temp_dir.path().join("dir_b").join("notes.md"), "").unwrap();
+
+        assert!(panel.go_to_path(&temp_dir.path().join("dir_b").join("notes.md")).unwrap());
+        assert_eq!(panel.current_path, temp_dir.path().join("dir_b"));
+        assert_eq!(panel.selected_entry().unwrap().name, "notes.md");
+
+        assert!(panel.go_to_path(&temp_dir.path().join("dir_a")).unwrap());
+        assert_eq!(panel.current_path, temp_dir.path().join("dir_a"));
+        assert!(!panel.go_to_path(&temp_dir.path().join("missing")).unwrap());
+        assert_eq!(panel.current_path, temp_dir.path().join("dir_a"));
+    }
+
+    #[test]
+    fn test_refresh_falls_back_to_parent() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        let nested = temp_dir.path().join("dir_a").join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        panel.change_directory(&nested).unwrap();
+
+        std::fs::remove_dir_all(temp_dir.path().join("dir_a")).unwrap();
+        assert!(panel.refresh_or_parent().is_err());
+        assert_eq!(panel.current_path, temp_dir.path());
+        assert!(panel.refresh_or_parent().is_ok());
+    }
+
+    #[test]
+    fn test_marks_cleared_on_directory_change() {
+        let (temp_dir, mut panel) = setup_test_panel();
+        panel.mark_matching("*", true);
+
+        panel.change_directory(&temp_dir.path().join("dir_a")).unwrap();
+        assert_eq!(panel.marked_count(), 0);
+    }
+
     #[test]
     fn test_scroll_adjustment() {
         let (_temp_dir, mut panel) = setup_test_panel();
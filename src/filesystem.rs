@@ -1,7 +1,6 @@
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 
 /// Represents a file or directory entry
@@ -12,7 +11,6 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub size: u64,
     pub modified: Option<DateTime<Local>>,
-    pub permissions: u32,
 }
 
 impl FileEntry {
@@ -27,15 +25,11 @@ impl FileEntry {
     pub fn format_date(&self) -> String {
         self.modified
             .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-            .unwrap_or_else(|| "".to_string())
-    }
-
-    pub fn format_permissions(&self) -> String {
-        format_unix_permissions(self.permissions)
+            .unwrap_or_default()
     }
 }
 
-fn format_file_size(size: u64) -> String {
+pub fn format_file_size(size: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -51,18 +45,32 @@ fn format_file_size(size: u64) -> String {
     }
 }
 
-fn format_unix_permissions(mode: u32) -> String {
-    let user = format_rwx((mode >> 6) & 0o7);
-    let group = format_rwx((mode >> 3) & 0o7);
-    let other = format_rwx(mode & 0o7);
-    format!("{}{}{}", user, group, other)
-}
+/// Match a file name against a shell-style pattern with `*` and `?` wildcards
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it is currently absorbing up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, absorbed)) = backtrack {
+            p = star + 1;
+            n = absorbed + 1;
+            backtrack = Some((star, absorbed + 1));
+        } else {
+            return false;
+        }
+    }
 
-fn format_rwx(bits: u32) -> String {
-    let r = if bits & 0o4 != 0 { 'r' } else { '-' };
-    let w = if bits & 0o2 != 0 { 'w' } else { '-' };
-    let x = if bits & 0o1 != 0 { 'x' } else { '-' };
-    format!("{}{}{}", r, w, x)
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 /// Trait for file system operations (enables local/remote abstraction)
@@ -70,6 +78,8 @@ pub trait FileSystem {
     fn list_directory(&self, path: &Path) -> Result<Vec<FileEntry>>;
     fn is_directory(&self, path: &Path) -> bool;
     fn exists(&self, path: &Path) -> bool;
+    /// The directory `~` stands for
+    fn home_dir(&self) -> PathBuf;
 }
 
 /// Local file system implementation
@@ -100,7 +110,6 @@ impl FileSystem for LocalFileSystem {
                 is_dir: true,
                 size: 0,
                 modified: None,
-                permissions: 0o755,
             });
         }
 
@@ -115,11 +124,7 @@ impl FileSystem for LocalFileSystem {
             let modified = metadata
                 .as_ref()
                 .and_then(|m| m.modified().ok())
-                .map(|t| DateTime::<Local>::from(t));
-            let permissions = metadata
-                .as_ref()
-                .map(|m| m.permissions().mode() & 0o777)
-                .unwrap_or(0);
+                .map(DateTime::<Local>::from);
 
             entries.push(FileEntry {
                 name: entry.file_name().to_string_lossy().to_string(),
@@ -127,25 +132,9 @@ impl FileSystem for LocalFileSystem {
                 is_dir,
                 size,
                 modified,
-                permissions,
             });
         }
 
-        // Sort: directories first, then by name
-        entries.sort_by(|a, b| {
-            if a.name == ".." {
-                std::cmp::Ordering::Less
-            } else if b.name == ".." {
-                std::cmp::Ordering::Greater
-            } else if a.is_dir && !b.is_dir {
-                std::cmp::Ordering::Less
-            } else if !a.is_dir && b.is_dir {
-                std::cmp::Ordering::Greater
-            } else {
-                a.name.to_lowercase().cmp(&b.name.to_lowercase())
-            }
-        });
-
         Ok(entries)
     }
 
@@ -156,6 +145,10 @@ impl FileSystem for LocalFileSystem {
     fn exists(&self, path: &Path) -> bool {
         path.exists()
     }
+
+    fn home_dir(&self) -> PathBuf {
+        dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"))
+    }
 }
 
 #[cfg(test)]
@@ -174,11 +167,17 @@ mod tests {
     }
 
     #[test]
-    fn test_format_permissions() {
-        assert_eq!(format_unix_permissions(0o755), "rwxr-xr-x");
-        assert_eq!(format_unix_permissions(0o644), "rw-r--r--");
-        assert_eq!(format_unix_permissions(0o777), "rwxrwxrwx");
-        assert_eq!(format_unix_permissions(0o000), "---------");
+    fn test_glob_match() {
+        assert!(glob_match("*", "anything.txt"));
+        assert!(glob_match("*.txt", "notes.txt"));
+        assert!(!glob_match("*.txt", "notes.txt.bak"));
+        assert!(glob_match("file?.rs", "file1.rs"));
+        assert!(!glob_match("file?.rs", "file10.rs"));
+        assert!(glob_match("*test*", "my_test_file"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "x"));
     }
 
     #[test]
@@ -195,13 +194,10 @@ mod tests {
         // Should have parent (..), subdir, and file.txt
         assert!(entries.len() >= 3);
         
-        // Parent should be first
+        // Parent should be first; the panel sorts the rest
         assert_eq!(entries[0].name, "..");
-        
-        // Directory should come before file
-        let subdir_pos = entries.iter().position(|e| e.name == "subdir").unwrap();
-        let file_pos = entries.iter().position(|e| e.name == "file.txt").unwrap();
-        assert!(subdir_pos < file_pos);
+        assert!(entries.iter().any(|e| e.name == "subdir" && e.is_dir));
+        assert!(entries.iter().any(|e| e.name == "file.txt" && !e.is_dir));
     }
 
     #[test]
@@ -212,11 +208,9 @@ mod tests {
             is_dir: false,
             size: 2048,
             modified: None,
-            permissions: 0o644,
         };
 
         assert_eq!(entry.format_size(), "2.0K");
-        assert_eq!(entry.format_permissions(), "rw-r--r--");
     }
 
     #[test]
@@ -227,7 +221,6 @@ mod tests {
             is_dir: true,
             size: 4096,
             modified: None,
-            permissions: 0o755,
         };
 
         assert_eq!(entry.format_size(), "<DIR>");
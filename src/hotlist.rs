@@ -0,0 +1,225 @@
+//! Named directory bookmarks, kept in the user's config directory
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Which filesystem a bookmark belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Local,
+    Remote(RemoteTarget),
+}
+
+impl Location {
+    pub fn label(&self) -> &str {
+        match self {
+            Location::Local => "local",
+            Location::Remote(target) => &target.host,
+        }
+    }
+}
+
+/// A remote host as given on the command line: user@host or an alias, and
+/// the jump hosts given with -J, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteTarget {
+    pub host: String,
+    pub jump: Option<String>,
+}
+
+impl RemoteTarget {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            jump: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub name: String,
+    pub location: Location,
+    pub path: PathBuf,
+}
+
+impl Bookmark {
+    /// One line of the hotlist file: name, location and path separated by tabs
+    fn to_line(&self) -> String {
+        let location = match &self.location {
+            Location::Local => "local".to_string(),
+            Location::Remote(RemoteTarget { host, jump: None }) => format!("ssh:{}", host),
+            Location::Remote(RemoteTarget {
+                host,
+                jump: Some(jump),
+            }) => format!("ssh:{} -J {}", host, jump),
+        };
+        format!("{}\t{}\t{}", self.name, location, self.path.display())
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, '\t');
+        let name = fields.next()?;
+        let location = match fields.next()? {
+            "local" => Location::Local,
+            other => {
+                let target = other.strip_prefix("ssh:")?;
+                Location::Remote(match target.split_once(" -J ") {
+                    Some((host, jump)) => RemoteTarget {
+                        host: host.to_string(),
+                        jump: Some(jump.to_string()),
+                    },
+                    None => RemoteTarget::new(target),
+                })
+            }
+        };
+        let path = fields.next()?;
+        if name.is_empty() || path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: name.to_string(),
+            location,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// The bookmarks and the file they are saved to
+#[derive(Debug, Clone, Default)]
+pub struct Hotlist {
+    path: Option<PathBuf>,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Hotlist {
+    /// Load the hotlist from the config directory, empty if there is none yet
+    pub fn load() -> Result<Self> {
+        match default_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the hotlist kept at `path`. Lines that cannot be read are skipped.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let bookmarks = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(Bookmark::parse_line)
+            .collect();
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            bookmarks,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cannot find the config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let mut contents = String::new();
+        for bookmark in &self.bookmarks {
+            contents.push_str(&bookmark.to_line());
+            contents.push('\n');
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.bookmarks.iter().any(|bookmark| bookmark.name == name)
+    }
+
+    pub fn add(&mut self, bookmark: Bookmark) {
+        self.bookmarks.push(bookmark);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Bookmark> {
+        (index < self.bookmarks.len()).then(|| self.bookmarks.remove(index))
+    }
+}
+
+/// ~/.config/remote-commander/hotlist, or the platform's equivalent
+fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("remote-commander").join("hotlist"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_lines() {
+        let local = Bookmark::parse_line("home\tlocal\t/home/me").unwrap();
+        assert_eq!(local.location, Location::Local);
+        assert_eq!(local.path, PathBuf::from("/home/me"));
+
+        // Paths may contain anything but a line break
+        let remote = Bookmark::parse_line("logs\tssh:deploy@web1\t/var/log/my\tapp").unwrap();
+        assert_eq!(remote.location, Location::Remote(RemoteTarget::new("deploy@web1")));
+        assert_eq!(remote.path, PathBuf::from("/var/log/my\tapp"));
+        assert_eq!(Bookmark::parse_line(&remote.to_line()), Some(remote));
+
+        // Hosts reached through jump hosts keep them
+        let jumped = Bookmark::parse_line("db\tssh:db1 -J admin@bastion,gw\t/srv").unwrap();
+        let Location::Remote(target) = &jumped.location else {
+            panic!("not remote: {:?}", jumped.location);
+        };
+        assert_eq!(target.host, "db1");
+        assert_eq!(target.jump.as_deref(), Some("admin@bastion,gw"));
+        assert_eq!(Bookmark::parse_line(&jumped.to_line()), Some(jumped));
+
+        assert_eq!(Bookmark::parse_line("logs\tftp:web1\t/var/log"), None);
+        assert_eq!(Bookmark::parse_line("no path\tlocal"), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config").join("hotlist");
+
+        let mut hotlist = Hotlist::load_from(&path).unwrap();
+        assert!(hotlist.bookmarks().is_empty());
+        hotlist.add(Bookmark {
+            name: "src".to_string(),
+            location: Location::Local,
+            path: PathBuf::from("/usr/src"),
+        });
+        hotlist.add(Bookmark {
+            name: "www".to_string(),
+            location: Location::Remote(RemoteTarget::new("web1")),
+            path: PathBuf::from("/var/www"),
+        });
+        hotlist.save().unwrap();
+
+        let mut loaded = Hotlist::load_from(&path).unwrap();
+        assert_eq!(loaded.bookmarks(), hotlist.bookmarks());
+        assert!(loaded.contains_name("www"));
+
+        assert_eq!(loaded.remove(0).unwrap().name, "src");
+        assert!(loaded.remove(5).is_none());
+        loaded.save().unwrap();
+        assert_eq!(Hotlist::load_from(&path).unwrap().bookmarks().len(), 1);
+    }
+}
@@ -0,0 +1,223 @@
+//! Single-line text editing for prompt dialogs
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Entries kept per kind of prompt
+const HISTORY_LIMIT: usize = 50;
+
+/// Previously entered values, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct InputHistory {
+    entries: Vec<String>,
+}
+
+impl InputHistory {
+    /// Remember `text` as the most recent entry
+    pub fn push(&mut self, text: &str) {
+        self.entries.retain(|entry| entry != text);
+        self.entries.push(text.to_string());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+/// An editable line of text with a cursor, counted in characters
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+    history: Vec<String>,
+    /// Entry shown while browsing the history with Up/Down
+    history_pos: Option<usize>,
+    /// What was typed before browsing started
+    draft: String,
+}
+
+impl TextInput {
+    /// Start with `initial` and the cursor at its end
+    pub fn new(initial: &str) -> Self {
+        Self {
+            text: initial.to_string(),
+            cursor: initial.chars().count(),
+            ..Self::default()
+        }
+    }
+
+    /// Let Up/Down recall earlier entries
+    pub fn with_history(mut self, history: &InputHistory) -> Self {
+        self.history = history.entries().to_vec();
+        self
+    }
+
+    /// Replace the text, moving the cursor to its end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.len();
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Apply an editing key, returning false if the key is not an editing key
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.len(),
+            KeyCode::Char('u') if ctrl => {
+                let at = self.byte_index(self.cursor);
+                self.text.drain(..at);
+                self.cursor = 0;
+            }
+            KeyCode::Char(_) if ctrl => return false,
+            KeyCode::Char(c) => {
+                let at = self.byte_index(self.cursor);
+                self.text.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let at = self.byte_index(self.cursor);
+                    self.text.remove(at);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.len() {
+                    let at = self.byte_index(self.cursor);
+                    self.text.remove(at);
+                }
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            KeyCode::Up => return self.history_back(),
+            KeyCode::Down => return self.history_forward(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn history_back(&mut self) -> bool {
+        let pos = match self.history_pos {
+            _ if self.history.is_empty() => return false,
+            None => {
+                self.draft = self.text.clone();
+                self.history.len() - 1
+            }
+            Some(pos) => pos.saturating_sub(1),
+        };
+        self.history_pos = Some(pos);
+        let entry = self.history[pos].clone();
+        self.set_text(&entry);
+        true
+    }
+
+    fn history_forward(&mut self) -> bool {
+        match self.history_pos {
+            None => return false,
+            Some(pos) if pos + 1 < self.history.len() => {
+                self.history_pos = Some(pos + 1);
+                let entry = self.history[pos + 1].clone();
+                self.set_text(&entry);
+            }
+            Some(_) => {
+                // Past the newest entry: back to what was being typed
+                self.history_pos = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_text(&draft);
+            }
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map(|(i, _)| i)
+            .unwrap_or(self.text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut TextInput, code: KeyCode) {
+        input.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn test_insert_and_move_cursor() {
+        let mut input = TextInput::new("ac");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Char('b'));
+        assert_eq!(input.text(), "abc");
+        assert_eq!(input.cursor(), 2);
+
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Delete);
+        assert_eq!(input.text(), "bc");
+
+        press(&mut input, KeyCode::End);
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.text(), "b");
+        assert_eq!(input.cursor(), 1);
+    }
+
+    #[test]
+    fn test_multibyte_editing() {
+        let mut input = TextInput::new("größe");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.text(), "gröe");
+        assert_eq!(input.cursor(), 3);
+    }
+
+    #[test]
+    fn test_ctrl_u_clears_to_start() {
+        let mut input = TextInput::new("*.log");
+        press(&mut input, KeyCode::Left);
+        input.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(input.text(), "g");
+        assert_eq!(input.cursor(), 0);
+    }
+
+    #[test]
+    fn test_history_navigation() {
+        let mut history = InputHistory::default();
+        history.push("one");
+        history.push("two");
+        history.push("one");
+        assert_eq!(history.entries(), ["two", "one"]);
+
+        let mut input = TextInput::new("draft").with_history(&history);
+        press(&mut input, KeyCode::Up);
+        assert_eq!(input.text(), "one");
+        press(&mut input, KeyCode::Up);
+        press(&mut input, KeyCode::Up);
+        assert_eq!(input.text(), "two");
+        assert_eq!(input.cursor(), 3);
+
+        press(&mut input, KeyCode::Down);
+        assert_eq!(input.text(), "one");
+        press(&mut input, KeyCode::Down);
+        assert_eq!(input.text(), "draft");
+    }
+}
@@ -0,0 +1,356 @@
+//! Background transfer queue
+//!
+//! Copies and moves are handed to a single worker thread and run one after
+//! another, so the UI keeps redrawing while data is moving.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::transfer::{self, CopyMode, Endpoint, TransferItem, TransferProgress, TransferStats};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Copy,
+    Move,
+}
+
+impl JobKind {
+    /// Progressive verb used in the progress dialog title
+    pub fn verb(&self) -> &'static str {
+        match self {
+            JobKind::Copy => "Copying",
+            JobKind::Move => "Moving",
+        }
+    }
+}
+
+/// A copy or move request for the worker thread
+pub struct TransferJob {
+    pub kind: JobKind,
+    /// Label for the dialog and status bar: a file name or an item count
+    pub name: String,
+    pub source: Endpoint,
+    pub dest: Endpoint,
+    pub items: Vec<TransferItem>,
+    pub mode: CopyMode,
+}
+
+/// What a successful job did
+#[derive(Debug)]
+pub enum JobResult {
+    Copied(TransferStats),
+    Renamed,
+    Moved(TransferStats),
+    /// The data was copied but the source could not be removed afterwards
+    SourceKept(anyhow::Error),
+}
+
+/// A finished job, reported back to the UI thread
+pub struct JobOutcome {
+    pub kind: JobKind,
+    pub name: String,
+    pub cancelled: bool,
+    pub result: Result<JobResult>,
+}
+
+impl JobOutcome {
+    pub fn status_message(&self) -> String {
+        match &self.result {
+            Ok(JobResult::Copied(stats)) => format!(
+                "Copied {} ({} files, {} bytes)",
+                self.name, stats.files, stats.bytes
+            ),
+            Ok(JobResult::Renamed) => format!("Moved {}", self.name),
+            Ok(JobResult::Moved(stats)) => format!(
+                "Moved {} ({} files, {} bytes)",
+                self.name, stats.files, stats.bytes
+            ),
+            Ok(JobResult::SourceKept(e)) => {
+                format!("Copied but failed to delete source: {:#}", e)
+            }
+            Err(_) if self.cancelled => format!("Cancelled {}", self.name),
+            Err(e) => match self.kind {
+                JobKind::Copy => format!("Copy failed: {:#}", e),
+                JobKind::Move => format!("Move failed: {:#}", e),
+            },
+        }
+    }
+}
+
+/// A job that has been submitted but not yet reported back
+pub struct PendingJob {
+    pub kind: JobKind,
+    pub name: String,
+    pub progress: Arc<TransferProgress>,
+}
+
+/// FIFO queue of transfers served by one worker thread
+pub struct TransferQueue {
+    jobs: Sender<(TransferJob, Arc<TransferProgress>)>,
+    outcomes: Receiver<JobOutcome>,
+    pending: VecDeque<PendingJob>,
+}
+
+impl TransferQueue {
+    pub fn new() -> Self {
+        let (job_sender, job_receiver) = mpsc::channel();
+        let (outcome_sender, outcome_receiver) = mpsc::channel();
+
+        // The worker exits once the queue (and with it the job sender) is dropped
+        thread::spawn(move || run_worker(job_receiver, outcome_sender));
+
+        Self {
+            jobs: job_sender,
+            outcomes: outcome_receiver,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn submit(&mut self, job: TransferJob) {
+        let progress = Arc::new(TransferProgress::new());
+        self.pending.push_back(PendingJob {
+            kind: job.kind,
+            name: job.name.clone(),
+            progress: Arc::clone(&progress),
+        });
+        if self.jobs.send((job, progress)).is_err() {
+            // Worker is gone; nothing will ever report this job back
+            self.pending.pop_back();
+        }
+    }
+
+    /// Collect jobs that finished since the last call, without blocking
+    pub fn poll(&mut self) -> Vec<JobOutcome> {
+        let mut finished = Vec::new();
+        while let Ok(outcome) = self.outcomes.try_recv() {
+            // Jobs run strictly in submission order
+            self.pending.pop_front();
+            finished.push(outcome);
+        }
+        finished
+    }
+
+    /// The job the worker is currently running
+    pub fn active(&self) -> Option<&PendingJob> {
+        self.pending.front()
+    }
+
+    /// Number of jobs waiting behind the active one
+    pub fn queued_count(&self) -> usize {
+        self.pending.len().saturating_sub(1)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn cancel_active(&self) {
+        if let Some(job) = self.active() {
+            job.progress.cancel();
+        }
+    }
+}
+
+impl Default for TransferQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run_worker(
+    jobs: Receiver<(TransferJob, Arc<TransferProgress>)>,
+    outcomes: Sender<JobOutcome>,
+) {
+    for (job, progress) in jobs {
+        let result = run_job(&job, &progress);
+        let outcome = JobOutcome {
+            kind: job.kind,
+            name: job.name,
+            cancelled: progress.is_cancelled(),
+            result,
+        };
+        if outcomes.send(outcome).is_err() {
+            break;
+        }
+    }
+}
+
+fn run_job(job: &TransferJob, progress: &TransferProgress) -> Result<JobResult> {
+    if progress.is_cancelled() {
+        anyhow::bail!("Transfer cancelled");
+    }
+
+    match job.kind {
+        JobKind::Copy => {
+            let stats = copy_data(job, &job.items, progress)?;
+            Ok(JobResult::Copied(stats))
+        }
+        JobKind::Move => {
+            // Same filesystem: a single rename is instant and atomic
+            let mut remaining = Vec::new();
+            for item in &job.items {
+                if !transfer::rename_path(&job.source, &job.dest, &item.source_path, &item.dest_path)? {
+                    remaining.push(item.clone());
+                }
+            }
+            if remaining.is_empty() {
+                return Ok(JobResult::Renamed);
+            }
+
+            let stats = copy_data(job, &remaining, progress)?;
+
+            // Delete source files and trees
+            for item in &remaining {
+                let deleted = if item.is_dir {
+                    transfer::delete_directory(&job.source, &item.source_path)
+                } else {
+                    transfer::delete_file(&job.source, &item.source_path)
+                };
+                if let Err(e) = deleted {
+                    return Ok(JobResult::SourceKept(e));
+                }
+            }
+
+            Ok(JobResult::Moved(stats))
+        }
+    }
+}
+
+/// Copy items to their destinations, continuing a partial file if asked to
+fn copy_data(
+    job: &TransferJob,
+    items: &[TransferItem],
+    progress: &TransferProgress,
+) -> Result<TransferStats> {
+    match job.mode {
+        CopyMode::Overwrite => transfer::copy_files(&job.source, &job.dest, items, progress),
+        CopyMode::Resume | CopyMode::ResumeVerified => {
+            let [item] = items else {
+                anyhow::bail!("Only a single file can be resumed");
+            };
+            transfer::resume_file(
+                &job.source,
+                &job.dest,
+                &item.source_path,
+                &item.dest_path,
+                job.mode,
+                progress,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    fn wait_for_outcome(queue: &mut TransferQueue) -> JobOutcome {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Some(outcome) = queue.poll().pop() {
+                return outcome;
+            }
+            assert!(Instant::now() < deadline, "transfer job did not finish");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn local_job(kind: JobKind, source_path: PathBuf, dest_path: PathBuf) -> TransferJob {
+        TransferJob {
+            kind,
+            name: "file.txt".to_string(),
+            source: Endpoint::Local,
+            dest: Endpoint::Local,
+            items: vec![TransferItem {
+                is_dir: source_path.is_dir(),
+                source_path,
+                dest_path,
+            }],
+            mode: CopyMode::Overwrite,
+        }
+    }
+
+    #[test]
+    fn test_copy_job_runs_in_background() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("file.txt");
+        let dest = temp_dir.path().join("copy.txt");
+        fs::write(&source, "queued").unwrap();
+
+        let mut queue = TransferQueue::new();
+        queue.submit(local_job(JobKind::Copy, source.clone(), dest.clone()));
+        assert!(queue.is_busy());
+
+        let outcome = wait_for_outcome(&mut queue);
+
+        assert!(matches!(outcome.result, Ok(JobResult::Copied(_))));
+        assert!(!queue.is_busy());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "queued");
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_move_job_renames_locally() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("file.txt");
+        let dest = temp_dir.path().join("moved.txt");
+        fs::write(&source, "moved").unwrap();
+
+        let mut queue = TransferQueue::new();
+        queue.submit(local_job(JobKind::Move, source.clone(), dest.clone()));
+
+        let outcome = wait_for_outcome(&mut queue);
+
+        assert!(matches!(outcome.result, Ok(JobResult::Renamed)));
+        assert_eq!(outcome.status_message(), "Moved file.txt");
+        assert!(!source.exists());
+        assert!(dest.exists());
+    }
+
+    #[test]
+    fn test_copy_job_resumes_partial_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("file.txt");
+        let dest = temp_dir.path().join("partial.txt");
+        fs::write(&source, "resumable contents").unwrap();
+        fs::write(&dest, "resumable").unwrap();
+
+        let mut queue = TransferQueue::new();
+        let mut job = local_job(JobKind::Copy, source, dest.clone());
+        job.mode = CopyMode::ResumeVerified;
+        queue.submit(job);
+
+        let outcome = wait_for_outcome(&mut queue);
+
+        match outcome.result {
+            Ok(JobResult::Copied(stats)) => assert_eq!(stats.bytes, 9),
+            other => panic!("unexpected outcome: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "resumable contents");
+    }
+
+    #[test]
+    fn test_failed_job_reports_error() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut queue = TransferQueue::new();
+        queue.submit(local_job(
+            JobKind::Copy,
+            temp_dir.path().join("missing.txt"),
+            temp_dir.path().join("copy.txt"),
+        ));
+
+        let outcome = wait_for_outcome(&mut queue);
+
+        assert!(outcome.result.is_err());
+        assert!(!outcome.cancelled);
+        assert!(outcome.status_message().starts_with("Copy failed"));
+    }
+}
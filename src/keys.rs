@@ -0,0 +1,178 @@
+//! Translating key presses into the bytes an xterm sends to the shell
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+const ESC: u8 = 0x1b;
+
+/// Start and end markers of a bracketed paste
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Bytes for a key press, or `None` for keys an xterm sends nothing for.
+/// `application_cursor` is the DECCKM mode of the shell's screen.
+pub fn encode_key(key: KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let modifier = modifier_param(key.modifiers);
+
+    let bytes = match key.code {
+        KeyCode::Char(c) if ctrl => vec![control_char(c)?],
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace if ctrl => vec![0x08],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![ESC],
+        KeyCode::Null => vec![0],
+        KeyCode::Up => cursor_key(b'A', modifier, application_cursor),
+        KeyCode::Down => cursor_key(b'B', modifier, application_cursor),
+        KeyCode::Right => cursor_key(b'C', modifier, application_cursor),
+        KeyCode::Left => cursor_key(b'D', modifier, application_cursor),
+        KeyCode::Home => cursor_key(b'H', modifier, application_cursor),
+        KeyCode::End => cursor_key(b'F', modifier, application_cursor),
+        KeyCode::Insert => tilde_key(2, modifier),
+        KeyCode::Delete => tilde_key(3, modifier),
+        KeyCode::PageUp => tilde_key(5, modifier),
+        KeyCode::PageDown => tilde_key(6, modifier),
+        KeyCode::F(n @ 1..=4) => {
+            let last = b"PQRS"[n as usize - 1];
+            match modifier {
+                Some(m) => format!("\x1b[1;{}{}", m, last as char).into_bytes(),
+                None => vec![ESC, b'O', last],
+            }
+        }
+        KeyCode::F(n @ 5..=12) => {
+            let number = [15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5];
+            tilde_key(number, modifier)
+        }
+        _ => return None,
+    };
+
+    // Keys sent with a modifier parameter carry Alt in it; the others get
+    // an Esc in front
+    let plain = matches!(
+        key.code,
+        KeyCode::Char(_) | KeyCode::Enter | KeyCode::Tab | KeyCode::Backspace | KeyCode::Esc
+    );
+    if alt && (plain || modifier.is_none()) {
+        let mut prefixed = vec![ESC];
+        prefixed.extend(bytes);
+        return Some(prefixed);
+    }
+    Some(bytes)
+}
+
+/// Bytes for pasted text. Line breaks become carriage returns as if typed,
+/// and a bracketed paste is wrapped in markers the text cannot forge.
+pub fn encode_paste(text: &str, bracketed: bool) -> Vec<u8> {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if !bracketed {
+        return text.into_bytes();
+    }
+
+    let mut bytes = PASTE_START.to_vec();
+    bytes.extend(text.bytes().filter(|&b| b != ESC));
+    bytes.extend_from_slice(PASTE_END);
+    bytes
+}
+
+/// The xterm modifier parameter: 1 plus Shift 1, Alt 2, Ctrl 4
+fn modifier_param(modifiers: KeyModifiers) -> Option<u8> {
+    let mut param = 1;
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        param += 1;
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        param += 2;
+    }
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        param += 4;
+    }
+    (param > 1).then_some(param)
+}
+
+/// Arrows, Home and End: `CSI 1;m x` with modifiers, otherwise `SS3 x` in
+/// application cursor mode and `CSI x` in normal mode
+fn cursor_key(last: u8, modifier: Option<u8>, application_cursor: bool) -> Vec<u8> {
+    match modifier {
+        Some(m) => format!("\x1b[1;{}{}", m, last as char).into_bytes(),
+        None if application_cursor => vec![ESC, b'O', last],
+        None => vec![ESC, b'[', last],
+    }
+}
+
+/// Editing and function keys sent as `CSI n ~` or `CSI n;m ~`
+fn tilde_key(number: u8, modifier: Option<u8>) -> Vec<u8> {
+    match modifier {
+        Some(m) => format!("\x1b[{};{}~", number, m).into_bytes(),
+        None => format!("\x1b[{}~", number).into_bytes(),
+    }
+}
+
+/// The control character for Ctrl and `c`, as xterm maps it
+fn control_char(c: char) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => Some(c as u8 - b'a' + 1),
+        '@' | ' ' | '2' => Some(0),
+        '[' | '3' => Some(ESC),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '/' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Vec<u8> {
+        encode_key(KeyEvent::new(code, modifiers), false).unwrap()
+    }
+
+    #[test]
+    fn test_control_and_alt_chars() {
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), b"\x03");
+        assert_eq!(key(KeyCode::Char('d'), KeyModifiers::CONTROL), b"\x04");
+        assert_eq!(key(KeyCode::Char(' '), KeyModifiers::CONTROL), b"\x00");
+        assert_eq!(key(KeyCode::Char('b'), KeyModifiers::ALT), b"\x1bb");
+        assert_eq!(
+            key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT),
+            b"\x1b\x18"
+        );
+        assert_eq!(key(KeyCode::Char('é'), KeyModifiers::NONE), "é".as_bytes());
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), b"\r");
+    }
+
+    #[test]
+    fn test_cursor_keys_follow_decckm() {
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(encode_key(up, false).unwrap(), b"\x1b[A");
+        assert_eq!(encode_key(up, true).unwrap(), b"\x1bOA");
+
+        let ctrl_left = KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(encode_key(ctrl_left, true).unwrap(), b"\x1b[1;5D");
+        assert_eq!(key(KeyCode::Home, KeyModifiers::NONE), b"\x1b[H");
+        assert_eq!(key(KeyCode::End, KeyModifiers::SHIFT), b"\x1b[1;2F");
+    }
+
+    #[test]
+    fn test_editing_and_function_keys() {
+        assert_eq!(key(KeyCode::Delete, KeyModifiers::NONE), b"\x1b[3~");
+        assert_eq!(key(KeyCode::PageDown, KeyModifiers::ALT), b"\x1b[6;3~");
+        assert_eq!(key(KeyCode::F(1), KeyModifiers::NONE), b"\x1bOP");
+        assert_eq!(key(KeyCode::F(2), KeyModifiers::SHIFT), b"\x1b[1;2Q");
+        assert_eq!(key(KeyCode::F(5), KeyModifiers::NONE), b"\x1b[15~");
+        assert_eq!(key(KeyCode::F(12), KeyModifiers::CONTROL), b"\x1b[24;5~");
+        assert_eq!(encode_key(KeyEvent::new(KeyCode::F(13), KeyModifiers::NONE), false), None);
+    }
+
+    #[test]
+    fn test_paste() {
+        assert_eq!(encode_paste("ls\npwd\r\n", false), b"ls\rpwd\r");
+        assert_eq!(encode_paste("a\x1b[201~b", true), b"\x1b[200~a[201~b\x1b[201~");
+    }
+}
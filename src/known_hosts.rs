@@ -0,0 +1,306 @@
+//! Server host key verification against ~/.ssh/known_hosts
+
+use anyhow::{anyhow, Context, Result};
+use ssh2::{CheckResult, HashType, HostKeyType, KnownHostFileKind, KnownHosts, Session};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+
+use crate::base64;
+
+/// Verification failures that must not be retried with other credentials
+#[derive(Debug, thiserror::Error)]
+pub enum HostKeyError {
+    #[error("Host key verification failed: the key for {host} was not accepted")]
+    Rejected { host: String },
+    #[error(
+        "WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED for {host}!\n\
+         Someone could be eavesdropping on you right now (man-in-the-middle attack),\n\
+         or the host key has just been changed.\n\
+         The {key_type} key sent by the remote host is {fingerprint}.\n\
+         If the change is expected, remove the old entry from {}.\n\
+         Host key verification failed.",
+        path.display()
+    )]
+    Changed {
+        host: String,
+        key_type: &'static str,
+        fingerprint: String,
+        path: PathBuf,
+    },
+}
+
+/// Result of looking a server key up in known_hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    Known,
+    Unknown,
+    Changed,
+}
+
+/// The key a server presented during the handshake
+pub struct ServerKey {
+    key: Vec<u8>,
+    key_type: HostKeyType,
+    fingerprint: String,
+}
+
+impl ServerKey {
+    pub fn from_session(session: &Session) -> Result<Self> {
+        let (key, key_type) = session
+            .host_key()
+            .ok_or_else(|| anyhow!("Server did not send a host key"))?;
+        let hash = session
+            .host_key_hash(HashType::Sha256)
+            .ok_or_else(|| anyhow!("Cannot compute host key fingerprint"))?;
+
+        Ok(Self {
+            key: key.to_vec(),
+            key_type,
+            fingerprint: format!("SHA256:{}", base64::encode(hash, false)),
+        })
+    }
+
+    /// Key algorithm as OpenSSH names it in prompts
+    pub fn type_name(&self) -> &'static str {
+        match self.key_type {
+            HostKeyType::Rsa => "RSA",
+            HostKeyType::Dss => "DSA",
+            HostKeyType::Ecdsa256 | HostKeyType::Ecdsa384 | HostKeyType::Ecdsa521 => "ECDSA",
+            HostKeyType::Ed25519 => "ED25519",
+            HostKeyType::Unknown => "unknown",
+        }
+    }
+
+    /// OpenSSH-style SHA256 fingerprint
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
+
+/// The user's known_hosts file
+pub fn default_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+/// Check the server's key, asking `confirm` whether to trust a host seen for
+/// the first time. Accepted keys are appended to known_hosts.
+pub fn verify_host_key(
+    session: &Session,
+    host: &str,
+    port: u16,
+    confirm: &mut dyn FnMut(&str) -> Result<bool>,
+) -> Result<()> {
+    let server_key = ServerKey::from_session(session)?;
+    let path = default_path().ok_or_else(|| anyhow!("Cannot find home directory"))?;
+
+    match check(session, &path, host, port, &server_key)? {
+        HostKeyStatus::Known => Ok(()),
+        HostKeyStatus::Changed => Err(HostKeyError::Changed {
+            host: host.to_string(),
+            key_type: server_key.type_name(),
+            fingerprint: server_key.fingerprint().to_string(),
+            path,
+        }
+        .into()),
+        HostKeyStatus::Unknown => {
+            let question = format!(
+                "The authenticity of host '{}' can't be established.\n\
+                 {} key fingerprint is {}.\n\
+                 Are you sure you want to continue connecting (yes/no)? ",
+                host_pattern(host, port),
+                server_key.type_name(),
+                server_key.fingerprint()
+            );
+            if !confirm(&question)? {
+                return Err(HostKeyError::Rejected {
+                    host: host.to_string(),
+                }
+                .into());
+            }
+            add(session, &path, host, port, &server_key)
+        }
+    }
+}
+
+/// Look the key up in the known_hosts file at `path`
+pub fn check(
+    session: &Session,
+    path: &Path,
+    host: &str,
+    port: u16,
+    server_key: &ServerKey,
+) -> Result<HostKeyStatus> {
+    let known_hosts = load(session, path)?;
+
+    match known_hosts.check_port(host, port, &server_key.key) {
+        CheckResult::Match => Ok(HostKeyStatus::Known),
+        CheckResult::NotFound => Ok(HostKeyStatus::Unknown),
+        CheckResult::Mismatch => Ok(HostKeyStatus::Changed),
+        CheckResult::Failure => Err(anyhow!("Failed to check host key for {}", host)),
+    }
+}
+
+/// Append the key to the known_hosts file at `path`
+pub fn add(
+    session: &Session,
+    path: &Path,
+    host: &str,
+    port: u16,
+    server_key: &ServerKey,
+) -> Result<()> {
+    // Format the line through a scratch collection so existing entries are
+    // left exactly as they are
+    let mut scratch = session.known_hosts()?;
+    scratch.add(
+        &host_pattern(host, port),
+        &server_key.key,
+        "",
+        server_key.key_type.into(),
+    )?;
+    let entry = scratch
+        .hosts()?
+        .pop()
+        .ok_or_else(|| anyhow!("Failed to format known_hosts entry"))?;
+    let line = scratch.write_string(&entry, KnownHostFileKind::OpenSSH)?;
+
+    if let Some(dir) = path.parent() {
+        create_ssh_dir(dir)?;
+    }
+
+    // Make sure the new entry starts on its own line
+    let needs_newline = fs::read(path)
+        .map(|data| !data.is_empty() && !data.ends_with(b"\n"))
+        .unwrap_or(false);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", line.trim_end())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Read entries one line at a time, skipping lines libssh2 cannot parse
+/// (certificate authorities, unsupported key types)
+fn load(session: &Session, path: &Path) -> Result<KnownHosts> {
+    let mut known_hosts = session.known_hosts()?;
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(known_hosts),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        known_hosts.read_str(line, KnownHostFileKind::OpenSSH).ok();
+    }
+
+    Ok(known_hosts)
+}
+
+/// Host name as written to known_hosts: bracketed with the port unless it is 22
+fn host_pattern(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Create ~/.ssh readable only by the user, as OpenSSH expects
+fn create_ssh_dir(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_key(byte: u8) -> ServerKey {
+        ServerKey {
+            key: vec![byte; 51],
+            key_type: HostKeyType::Ed25519,
+            fingerprint: "SHA256:test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_host_pattern() {
+        assert_eq!(host_pattern("example.com", 22), "example.com");
+        assert_eq!(host_pattern("example.com", 2222), "[example.com]:2222");
+    }
+
+    #[test]
+    fn test_unknown_host_is_added() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(".ssh").join("known_hosts");
+        let session = Session::new().unwrap();
+        let key = test_key(1);
+
+        assert_eq!(
+            check(&session, &path, "example.com", 22, &key).unwrap(),
+            HostKeyStatus::Unknown
+        );
+
+        add(&session, &path, "example.com", 22, &key).unwrap();
+
+        assert!(fs::read_to_string(&path).unwrap().starts_with("example.com ssh-ed25519 "));
+        assert_eq!(
+            check(&session, &path, "example.com", 22, &key).unwrap(),
+            HostKeyStatus::Known
+        );
+    }
+
+    #[test]
+    fn test_changed_key_is_detected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("known_hosts");
+        let session = Session::new().unwrap();
+
+        add(&session, &path, "example.com", 22, &test_key(1)).unwrap();
+
+        assert_eq!(
+            check(&session, &path, "example.com", 22, &test_key(2)).unwrap(),
+            HostKeyStatus::Changed
+        );
+    }
+
+    #[test]
+    fn test_existing_entries_are_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("known_hosts");
+        fs::write(&path, "# my hosts\n@cert-authority *.corp ssh-ed25519 AAAA").unwrap();
+        let session = Session::new().unwrap();
+
+        add(&session, &path, "example.com", 22, &test_key(1)).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# my hosts\n@cert-authority *.corp ssh-ed25519 AAAA\n"));
+        assert_eq!(
+            check(&session, &path, "example.com", 22, &test_key(1)).unwrap(),
+            HostKeyStatus::Known
+        );
+    }
+}
@@ -1,29 +1,56 @@
 mod app;
+mod base64;
+mod editor;
 mod file_panel;
 mod filesystem;
+mod hotlist;
+mod input;
+mod jobs;
+mod keys;
+mod known_hosts;
+mod scrollback;
 mod shell;
 mod ssh;
+mod ssh_config;
 mod transfer;
+mod tunnel;
 mod ui;
+mod viewer;
+mod vt;
 
 use anyhow::{Context, Result};
 use app::App;
+use file_panel::SortKey;
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use hotlist::RemoteTarget;
+use known_hosts::HostKeyError;
 use ssh::{SshConnection, SshConnectionInfo};
+use ssh_config::SshConfig;
 use std::io::{self, Write};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Norton Commander-style dual-pane file manager with SSH support")]
 struct Args {
-    /// Remote connection string (e.g., user@hostname or user@hostname:port)
+    /// Remote connection string (e.g., user@hostname, user@hostname:port or a Host alias from ~/.ssh/config)
     #[arg(value_name = "USER@HOST")]
     remote: Option<String>,
+
+    /// Connect through jump hosts, like ssh -J (e.g., user@bastion or host1,host2)
+    #[arg(short = 'J', long = "jump", value_name = "USER@HOST[,...]")]
+    jump: Option<String>,
+
+    /// Lines of terminal history to keep for each shell
+    #[arg(long, value_name = "LINES", default_value_t = vt::DEFAULT_SCROLLBACK)]
+    scrollback: usize,
 }
 
 fn main() -> Result<()> {
@@ -31,7 +58,7 @@ fn main() -> Result<()> {
 
     // If remote connection specified, establish SSH before entering TUI
     let ssh_connection = if let Some(ref remote_str) = args.remote {
-        Some(establish_ssh_connection(remote_str)?)
+        Some(establish_ssh_connection(remote_str, args.jump.as_deref())?)
     } else {
         None
     };
@@ -39,12 +66,17 @@ fn main() -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Create app state
-    let mut app = App::new(args.remote, ssh_connection)?;
+    let target = args.remote.map(|host| RemoteTarget {
+        host,
+        jump: args.jump,
+    });
+    let mut app = App::new(target, ssh_connection)?;
+    app.set_scrollback_limit(args.scrollback);
 
     // Main loop
     let result = run_app(&mut terminal, &mut app);
@@ -54,7 +86,8 @@ fn main() -> Result<()> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
@@ -65,18 +98,37 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn establish_ssh_connection(connection_string: &str) -> Result<SshConnection> {
-    let info = SshConnectionInfo::parse(connection_string)?;
-    
-    println!("Connecting to {}@{}:{}...", info.username, info.hostname, info.port);
+fn establish_ssh_connection(connection_string: &str, jump: Option<&str>) -> Result<SshConnection> {
+    let config = SshConfig::load()?;
+    let mut info = SshConnectionInfo::resolve(connection_string, &config)?;
+
+    // -J takes precedence over ProxyJump from the config file
+    if let Some(spec) = jump {
+        info.jump_hosts = SshConnectionInfo::resolve_jump_hosts(spec, &config)?;
+    }
+
+    let via: Vec<String> = info.jump_hosts.iter().map(|hop| hop.hostname.clone()).collect();
+    if via.is_empty() {
+        println!("Connecting to {}@{}:{}...", info.username, info.hostname, info.port);
+    } else {
+        println!(
+            "Connecting to {}@{}:{} via {}...",
+            info.username,
+            info.hostname,
+            info.port,
+            via.join(", ")
+        );
+    }
     io::stdout().flush()?;
 
     // First try with SSH key
-    match SshConnection::connect(info.clone(), None) {
+    match SshConnection::connect(info.clone(), None, &mut confirm_host_key) {
         Ok(conn) => {
             println!("Connected using SSH key.");
             return Ok(conn);
         }
+        // A password will not fix an untrusted host key
+        Err(e) if e.downcast_ref::<HostKeyError>().is_some() => return Err(e),
         Err(_) => {
             // SSH key failed, prompt for password
             println!("SSH key authentication failed or not available.");
@@ -87,7 +139,7 @@ fn establish_ssh_connection(connection_string: &str) -> Result<SshConnection> {
     let password = rpassword::prompt_password(format!("{}@{}'s password: ", info.username, info.hostname))
         .context("Failed to read password")?;
 
-    let connection = SshConnection::connect(info, Some(&password))
+    let connection = SshConnection::connect(info, Some(&password), &mut confirm_host_key)
         .context("SSH connection failed")?;
     
     println!("Connected.");
@@ -95,61 +147,261 @@ fn establish_ssh_connection(connection_string: &str) -> Result<SshConnection> {
     Ok(connection)
 }
 
+/// Ask on the console whether to trust a host seen for the first time
+fn confirm_host_key(question: &str) -> Result<bool> {
+    print!("{}", question);
+    loop {
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to read answer")?;
+        match answer.trim().to_lowercase().as_str() {
+            "yes" => {
+                println!("Adding the host key to the list of known hosts.");
+                return Ok(true);
+            }
+            "no" | "" => return Ok(false),
+            _ => print!("Please type 'yes' or 'no': "),
+        }
+    }
+}
+
+/// Leave the TUI, run `f` on the plain console, then restore the TUI
+fn with_tui_suspended<B: ratatui::backend::Backend, R>(
+    terminal: &mut Terminal<B>,
+    f: impl FnOnce() -> Result<R>,
+) -> Result<R> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)?;
+
+    let result = f();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    terminal.clear()?;
+
+    result
+}
+
+/// Set the clipboard of the terminal we run in with OSC 52
+fn copy_to_system_clipboard(text: &str) -> Result<()> {
+    let encoded = base64::encode(text.as_bytes(), true);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()?;
+    Ok(())
+}
+
 fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     loop {
+        app.poll_transfers()?;
+        app.sync_shell_directories();
+
+        // Hand the terminal to the external editor while it runs
+        if let Some(session) = app.pending_edit.take() {
+            let result = with_tui_suspended(terminal, || editor::run_editor(session.local_path()));
+            app.finish_edit(session, result)?;
+        }
+
+        // Connect to a bookmarked host on the console, where the password
+        // and host key prompts can be answered
+        if let Some(bookmark) = app.pending_connection.take() {
+            if let hotlist::Location::Remote(target) = &bookmark.location {
+                let result = with_tui_suspended(terminal, || {
+                    establish_ssh_connection(&target.host, target.jump.as_deref())
+                });
+                app.finish_connection(bookmark, result)?;
+            }
+        }
+
+        // Text copied from the terminal also goes to the system clipboard
+        if let Some(text) = app.copied_text.take() {
+            copy_to_system_clipboard(&text)?;
+        }
+
         terminal.draw(|f| ui::draw(f, app))?;
 
         // Use polling with timeout to reduce CPU usage and improve responsiveness
         if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+
+            // Resize the shells before the next draw, so programs running in
+            // them redraw for the new size straight away
+            if let Event::Resize(cols, rows) = event {
+                let area = ui::terminal_inner_area(ratatui::layout::Rect::new(0, 0, cols, rows), app);
+                app.resize_shells(area.height, area.width);
+            }
+
+            if let Event::Paste(text) = &event {
+                if let Some(dialog) = app.input_dialog.as_mut() {
+                    // Typed into the prompt one character at a time
+                    for c in text.chars().filter(|c| !c.is_control()) {
+                        dialog.input.handle_key(KeyEvent::from(KeyCode::Char(c)));
+                    }
+                } else if app.terminal_visible() && app.terminal_input_mode && app.terminal_scroll.is_none() {
+                    let _ = app.paste_to_shell(text);
+                }
+            }
+
+            if let Event::Key(key) = event {
                 if key.kind == KeyEventKind::Press {
                     // Handle confirmation dialog keys if active
                     if app.confirmation_dialog.is_some() {
                     match key.code {
-                        KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('o') | KeyCode::Char('O') => {
                             app.confirm_action()?;
                         }
+                        KeyCode::Char('r') | KeyCode::Char('R') => app.confirm_resume(false),
+                        KeyCode::Char('v') | KeyCode::Char('V') => app.confirm_resume(true),
                         KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
                             app.cancel_confirmation();
                         }
                         _ => {}
                     }
-                } else if app.show_terminal && app.terminal_input_mode {
-                    // Terminal input mode - send ALL keys to shell except Esc
+                } else if let Some(dialog) = app.input_dialog.as_mut() {
+                    // Prompt dialog takes all keys until dismissed
                     match key.code {
-                        KeyCode::Esc => {
-                            // Toggle terminal input mode off, return to navigation
-                            app.exit_terminal_input_mode();
+                        KeyCode::Esc => app.cancel_input(),
+                        KeyCode::Enter => app.submit_input()?,
+                        KeyCode::Tab if dialog.action == app::InputAction::GoToPath => {
+                            app.complete_input_path();
                         }
-                        KeyCode::Char(c) => {
-                            let _ = app.send_to_shell(c.to_string().as_bytes());
+                        _ => {
+                            dialog.error = None;
+                            dialog.input.handle_key(key);
                         }
-                        KeyCode::Enter => {
-                            let _ = app.send_to_shell(b"\n");
+                    }
+                } else if app.transfer_dialog_visible() {
+                    // Progress dialog is modal while a transfer runs
+                    match key.code {
+                        KeyCode::Esc => app.cancel_transfer(),
+                        KeyCode::Char('b') | KeyCode::Char('B') | KeyCode::Enter => {
+                            app.background_transfer();
                         }
-                        KeyCode::Backspace => {
-                            let _ = app.send_to_shell(b"\x7f");
+                        _ => {}
+                    }
+                } else if app.recent_popup.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.close_recent_directories(),
+                        KeyCode::Enter => app.open_recent_directory()?,
+                        KeyCode::Up => app.move_recent_selection(false),
+                        KeyCode::Down => app.move_recent_selection(true),
+                        _ => {}
+                    }
+                } else if app.hotlist_popup.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.close_hotlist(),
+                        KeyCode::Char('4') | KeyCode::Char('\\')
+                            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            app.close_hotlist();
                         }
+                        KeyCode::Enter => app.open_bookmark()?,
+                        KeyCode::Up => app.move_hotlist_selection(false),
+                        KeyCode::Down => app.move_hotlist_selection(true),
+                        KeyCode::Insert | KeyCode::Char('a') => app.prompt_add_bookmark(),
+                        KeyCode::Delete | KeyCode::Char('d') => app.remove_bookmark(),
+                        _ => {}
+                    }
+                } else if let Some(viewer) = app.viewer.as_mut() {
+                    // Viewer keys; a message stays up until the next key
+                    viewer.message = None;
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::F(3) | KeyCode::F(10) => {
+                            app.close_viewer();
+                        }
+                        KeyCode::Up => viewer.scroll_up(1),
+                        KeyCode::Down | KeyCode::Enter => viewer.scroll_down(1),
+                        KeyCode::PageUp => viewer.page_up(),
+                        KeyCode::PageDown | KeyCode::Char(' ') => viewer.page_down(),
+                        KeyCode::Left => viewer.scroll_left(),
+                        KeyCode::Right => viewer.scroll_right(),
+                        KeyCode::Home => viewer.move_to_first(),
+                        KeyCode::End => viewer.move_to_last(),
+                        KeyCode::F(2) | KeyCode::Char('w') => viewer.toggle_wrap(),
+                        KeyCode::F(4) | KeyCode::Char('h') => viewer.toggle_hex(),
+                        KeyCode::Char('n') => viewer.search_next(),
+                        KeyCode::F(5) | KeyCode::Char('g') => app.prompt_viewer_goto(),
+                        KeyCode::F(7) | KeyCode::Char('/') => app.prompt_viewer_search(),
+                        _ => {}
+                    }
+                } else if key.code == KeyCode::Char('o') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.toggle_fullscreen_terminal();
+                } else if app.terminal_visible() && app.terminal_scroll.is_some() {
+                    // Browsing the terminal history
+                    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => app.exit_scrollback(),
                         KeyCode::Up => {
-                            let _ = app.send_to_shell(b"\x1b[A"); // Up arrow
+                            app.with_scrollback(|view, screen| view.move_cursor(-1, screen));
                         }
                         KeyCode::Down => {
-                            let _ = app.send_to_shell(b"\x1b[B"); // Down arrow
+                            app.with_scrollback(|view, screen| view.move_cursor(1, screen));
                         }
-                        KeyCode::Left => {
-                            let _ = app.send_to_shell(b"\x1b[D"); // Left arrow
+                        KeyCode::PageUp => app.scroll_terminal_back(),
+                        KeyCode::PageDown if shift => app.scroll_terminal_forward(),
+                        KeyCode::PageDown => {
+                            app.with_scrollback(|view, screen| view.page_down(screen));
                         }
-                        KeyCode::Right => {
-                            let _ = app.send_to_shell(b"\x1b[C"); // Right arrow
+                        KeyCode::Home => {
+                            app.with_scrollback(|view, screen| view.move_to_first(screen));
                         }
-                        KeyCode::Tab => {
-                            let _ = app.send_to_shell(b"\t");
+                        KeyCode::End => {
+                            app.with_scrollback(|view, screen| view.move_to_last(screen));
+                        }
+                        KeyCode::F(7) | KeyCode::Char('/') => app.prompt_terminal_search(),
+                        KeyCode::Char('n') => app.search_terminal_next(true),
+                        KeyCode::Char('N') => app.search_terminal_next(false),
+                        KeyCode::Char(' ') | KeyCode::Char('v') => {
+                            app.with_scrollback(|view, _| view.toggle_selection());
                         }
+                        KeyCode::Enter | KeyCode::Char('y') => app.copy_terminal_selection(),
                         _ => {}
                     }
+                } else if app.terminal_visible() && app.terminal_input_mode {
+                    // Terminal input mode - send ALL keys to shell except Esc
+                    match key.code {
+                        KeyCode::Esc => {
+                            let _ = app.terminal_escape();
+                        }
+                        KeyCode::PageUp if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.scroll_terminal_back();
+                        }
+                        KeyCode::Insert if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            let _ = app.paste_clipboard();
+                        }
+                        _ => {
+                            let _ = app.send_key_to_shell(key);
+                        }
+                    }
+                } else if app.handle_quick_search_key(key) {
+                    // Typed into the panel's quick search
                 } else {
                     // Navigation mode - normal key handling
                     match key.code {
+                        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.prompt_filter();
+                        }
+                        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.prompt_go_to_path();
+                        }
+                        KeyCode::Char(':') => app.prompt_go_to_path(),
+                        // Ctrl-\ arrives as Ctrl-4 from most terminals
+                        KeyCode::Char('4') | KeyCode::Char('\\')
+                            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            app.show_hotlist();
+                        }
+                        KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => app.go_back()?,
+                        KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => app.go_forward()?,
+                        KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                            app.show_recent_directories();
+                        }
+                        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+                            app.active_panel_mut().quick_search_push(c);
+                        }
+                        KeyCode::Char('.') => app.toggle_hidden_files()?,
                         KeyCode::Char('q') | KeyCode::F(10) => return Ok(()),
                         KeyCode::Tab => {
                             app.toggle_active_panel();
@@ -167,15 +419,38 @@ fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut A
                         KeyCode::Backspace => app.go_parent_directory()?,
                         KeyCode::Home => app.move_to_first(),
                         KeyCode::End => app.move_to_last(),
+                        KeyCode::PageUp
+                            if app.show_terminal && key.modifiers.contains(KeyModifiers::SHIFT) =>
+                        {
+                            app.scroll_terminal_back();
+                        }
                         KeyCode::PageUp => app.page_up(),
                         KeyCode::PageDown => app.page_down(),
+                        KeyCode::F(n @ 3..=8) if key.modifiers.contains(KeyModifiers::CONTROL) => match n {
+                            3 => app.sort_by(SortKey::Name),
+                            4 => app.sort_by(SortKey::Extension),
+                            5 => app.sort_by(SortKey::Modified),
+                            6 => app.sort_by(SortKey::Size),
+                            7 => app.sort_by(SortKey::Unsorted),
+                            _ => app.toggle_dirs_first(),
+                        },
                         KeyCode::F(1) | KeyCode::Char('h') => app.show_help(),
+                        KeyCode::F(3) | KeyCode::Char('v') => app.view_file()?,
                         KeyCode::F(4) | KeyCode::Char('e') => app.edit_file()?,
                         KeyCode::F(5) | KeyCode::Char('c') => app.copy_file()?,
+                        KeyCode::F(6) if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.rename_file()?;
+                        }
+                        KeyCode::Char('r') => app.rename_file()?,
                         KeyCode::F(6) | KeyCode::Char('m') => app.move_file()?,
                         KeyCode::F(7) | KeyCode::Char('n') => app.make_directory()?,
                         KeyCode::F(8) | KeyCode::Char('d') => app.delete_file()?,
                         KeyCode::F(9) | KeyCode::Char('t') => app.toggle_terminal(),
+                        KeyCode::Char('j') => app.show_transfers(),
+                        KeyCode::Insert | KeyCode::Char(' ') => app.toggle_mark(),
+                        KeyCode::Char('+') => app.prompt_mark_pattern(true),
+                        KeyCode::Char('-') => app.prompt_mark_pattern(false),
+                        KeyCode::Char('*') => app.invert_marks(),
                         _ => {}
                     }
                 }
@@ -0,0 +1,235 @@
+//! Browsing, searching and copying the terminal history
+//!
+//! Positions are absolute line numbers from `vt::Screen`, so the view stays
+//! on the same text while the shell keeps printing.
+
+use crate::vt::Screen;
+
+/// State of the terminal pane while it shows history instead of the live screen
+pub struct ScrollbackView {
+    /// Line shown at the top of the pane
+    top: u64,
+    /// Line the cursor is on
+    cursor: u64,
+    /// Where the selection started, if one is being made
+    anchor: Option<u64>,
+    page_lines: usize,
+    last_search: Option<String>,
+}
+
+impl ScrollbackView {
+    /// Start at the bottom of the live screen
+    pub fn new(screen: &Screen, page_lines: usize) -> Self {
+        let last = screen.end_line().saturating_sub(1);
+        let mut view = Self {
+            top: 0,
+            cursor: last,
+            anchor: None,
+            page_lines: page_lines.max(1),
+            last_search: None,
+        };
+        view.top = (last + 1).saturating_sub(view.page_lines as u64);
+        view.clamp(screen);
+        view
+    }
+
+    pub fn top(&self) -> u64 {
+        self.top
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub fn last_search(&self) -> Option<&str> {
+        self.last_search.as_deref()
+    }
+
+    /// The selected lines, first to last
+    pub fn selection(&self) -> Option<(u64, u64)> {
+        self.anchor
+            .map(|anchor| (anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    /// Record how many lines the pane shows, for paging
+    pub fn set_page_lines(&mut self, lines: usize, screen: &Screen) {
+        self.page_lines = lines.max(1);
+        self.clamp(screen);
+    }
+
+    /// Whether the view has reached the live screen
+    pub fn at_bottom(&self, screen: &Screen) -> bool {
+        self.top + self.page_lines as u64 >= screen.end_line()
+    }
+
+    /// Lines between the view and the live screen
+    pub fn lines_back(&self, screen: &Screen) -> u64 {
+        screen
+            .end_line()
+            .saturating_sub(self.top + self.page_lines as u64)
+    }
+
+    pub fn move_cursor(&mut self, delta: i64, screen: &Screen) {
+        self.cursor = self.cursor.saturating_add_signed(delta);
+        self.clamp(screen);
+    }
+
+    pub fn page_up(&mut self, screen: &Screen) {
+        let page = self.page_lines.saturating_sub(1).max(1) as u64;
+        self.top = self.top.saturating_sub(page);
+        self.cursor = self.cursor.saturating_sub(page);
+        self.clamp(screen);
+    }
+
+    pub fn page_down(&mut self, screen: &Screen) {
+        let page = self.page_lines.saturating_sub(1).max(1) as u64;
+        self.top += page;
+        self.cursor += page;
+        self.clamp(screen);
+    }
+
+    pub fn move_to_first(&mut self, screen: &Screen) {
+        self.top = screen.first_line();
+        self.cursor = self.top;
+        self.clamp(screen);
+    }
+
+    pub fn move_to_last(&mut self, screen: &Screen) {
+        self.top = screen.end_line();
+        self.cursor = self.top;
+        self.clamp(screen);
+    }
+
+    /// Start a selection at the cursor, or drop the current one
+    pub fn toggle_selection(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor),
+        };
+    }
+
+    /// Text of the selection, or of the cursor line if nothing is selected.
+    /// Rows the terminal wrapped are joined back into one line.
+    pub fn selected_text(&self, screen: &Screen) -> String {
+        let (first, last) = self.selection().unwrap_or((self.cursor, self.cursor));
+        let mut text = String::new();
+        for line in first..=last {
+            let Some(row) = screen.line(line) else {
+                continue;
+            };
+            text.push_str(&row.text());
+            if line != last && !row.wrapped {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Find `text` (ignoring case) above the cursor, or below it when
+    /// `backward` is false, and move the cursor there
+    pub fn search(&mut self, text: &str, backward: bool, screen: &Screen) -> bool {
+        self.last_search = Some(text.to_string());
+        let needle = text.to_lowercase();
+        let matches = |line: &u64| {
+            screen
+                .line(*line)
+                .is_some_and(|row| row.text().to_lowercase().contains(&needle))
+        };
+
+        let found = if backward {
+            (screen.first_line()..self.cursor).rev().find(matches)
+        } else {
+            (self.cursor + 1..screen.end_line()).find(matches)
+        };
+        match found {
+            Some(line) => {
+                self.cursor = line;
+                // Show the match in the middle of the pane when jumping
+                if line < self.top || line >= self.top + self.page_lines as u64 {
+                    self.top = line.saturating_sub(self.page_lines as u64 / 2);
+                }
+                self.clamp(screen);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep the view and cursor on lines that still exist, and the cursor visible
+    fn clamp(&mut self, screen: &Screen) {
+        let first = screen.first_line();
+        let last = screen.end_line().saturating_sub(1);
+        let page = self.page_lines as u64;
+
+        self.cursor = self.cursor.clamp(first, last);
+        let max_top = (last + 1).saturating_sub(page).max(first);
+        self.top = self.top.clamp(first, max_top);
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + page {
+            self.top = self.cursor + 1 - page;
+        }
+        if let Some(anchor) = self.anchor.as_mut() {
+            *anchor = (*anchor).clamp(first, last);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with_history() -> Screen {
+        let mut screen = Screen::new(3, 20);
+        for i in 1..=10 {
+            screen.process(format!("line {}\r\n", i).as_bytes());
+        }
+        screen.process(b"$ ");
+        screen
+    }
+
+    #[test]
+    fn test_paging_through_history() {
+        let screen = screen_with_history();
+        let mut view = ScrollbackView::new(&screen, 3);
+        assert!(view.at_bottom(&screen));
+
+        view.page_up(&screen);
+        assert_eq!(view.lines_back(&screen), 2);
+        view.move_to_first(&screen);
+        assert_eq!(screen.line(view.top()).unwrap().text(), "line 1");
+
+        view.move_to_last(&screen);
+        assert!(view.at_bottom(&screen));
+        assert_eq!(screen.line(view.cursor()).unwrap().text(), "$");
+    }
+
+    #[test]
+    fn test_search_moves_cursor() {
+        let screen = screen_with_history();
+        let mut view = ScrollbackView::new(&screen, 3);
+
+        assert!(view.search("LINE 3", true, &screen));
+        assert_eq!(screen.line(view.cursor()).unwrap().text(), "line 3");
+        assert!(view.cursor() >= view.top() && view.cursor() < view.top() + 3);
+
+        assert!(!view.search("line 3", true, &screen));
+        assert!(view.search("line 1", false, &screen));
+        assert_eq!(screen.line(view.cursor()).unwrap().text(), "line 10");
+    }
+
+    #[test]
+    fn test_selected_text_joins_wrapped_rows() {
+        let mut screen = Screen::new(4, 5);
+        screen.process(b"abcdefgh\r\nxy");
+        let mut view = ScrollbackView::new(&screen, 4);
+
+        view.move_to_first(&screen);
+        view.toggle_selection();
+        view.move_cursor(2, &screen);
+        assert_eq!(view.selected_text(&screen), "abcdefgh\nxy");
+
+        view.toggle_selection();
+        assert_eq!(view.selected_text(&screen), "xy");
+    }
+}
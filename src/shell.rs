@@ -1,11 +1,35 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use portable_pty::{CommandBuilder, MasterPty, PtySize, native_pty_system};
-use ssh2::Channel;
-use std::io::{Read, Write};
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::vt::Screen;
 
 const BUFFER_SIZE: usize = 8192;
 
+/// Terminal type announced to shells; matches what `vt::Screen` emulates
+const TERM: &str = "xterm-256color";
+
+/// Size of a new terminal until the pane size is known
+const INITIAL_ROWS: u16 = 24;
+const INITIAL_COLS: u16 = 80;
+
+/// How long the remote shell waits before polling its channel again
+const READ_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How many times a closing remote shell retries a channel close that would block
+const CLOSE_ATTEMPTS: usize = 50;
+
+/// Typed into new bash and zsh shells so they report their directory
+/// (OSC 7) before every prompt. Shells that already do so are unaffected.
+/// `%`, BEL and ESC are percent-encoded, as the URL is decoded again.
+const PROMPT_HOOK: &str = r#"__rc_cwd() { local p="${PWD//[%]/%25}"; p="${p//$'\a'/%07}"; p="${p//$'\e'/%1B}"; printf '\033]7;file://%s%s\007' "${HOSTNAME:-$HOST}" "$p"; }; if [ -n "$ZSH_VERSION" ]; then eval 'precmd_functions+=(__rc_cwd)'; else PROMPT_COMMAND="__rc_cwd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"; fi
+"#;
+
 pub enum ShellType {
     Local(LocalShell),
     Remote(RemoteShell),
@@ -13,41 +37,81 @@ pub enum ShellType {
 
 pub struct LocalShell {
     master: Box<dyn MasterPty + Send>,
-    writer: Box<dyn Write + Send>,
-    output_buffer: Arc<Mutex<Vec<u8>>>,
-    cached_output: Arc<Mutex<String>>,
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    screen: Arc<Mutex<Screen>>,
+    dir_sync: DirectorySync,
 }
 
 pub struct RemoteShell {
-    channel: Channel,
-    output_buffer: Arc<Mutex<Vec<u8>>>,
-    _reader_thread: Option<std::thread::JoinHandle<()>>,
+    channel: Arc<Mutex<Channel>>,
+    screen: Arc<Mutex<Screen>>,
+    dir_sync: DirectorySync,
+    /// Tells the reader thread to let go of the channel
+    stop: Arc<AtomicBool>,
+    reader_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+/// What to do to keep a shell and its panel in the same directory
+#[derive(Debug, PartialEq, Eq)]
+enum SyncAction {
+    /// Change the panel to the shell's new directory
+    FollowShell(String),
+    /// `cd` the idle shell to the panel's directory
+    ChangeShell(String),
+}
+
+/// The directories last seen on each side, so only changes are acted on
+#[derive(Default)]
+struct DirectorySync {
+    shell_dir: Option<String>,
+    panel_dir: Option<String>,
+}
+
+impl DirectorySync {
+    fn update(&mut self, shell_dir: Option<&str>, at_prompt: bool, panel_dir: &str) -> Option<SyncAction> {
+        let shell_dir = shell_dir?;
+
+        if self.shell_dir.as_deref() != Some(shell_dir) {
+            self.shell_dir = Some(shell_dir.to_string());
+            self.panel_dir = Some(panel_dir.to_string());
+            return (shell_dir != panel_dir).then(|| SyncAction::FollowShell(shell_dir.to_string()));
+        }
+
+        if at_prompt && self.panel_dir.as_deref() != Some(panel_dir) {
+            self.panel_dir = Some(panel_dir.to_string());
+            return (shell_dir != panel_dir).then(|| SyncAction::ChangeShell(panel_dir.to_string()));
+        }
+
+        None
+    }
 }
 
 impl LocalShell {
-    pub fn new() -> Result<Self> {
+    /// Start the user's shell, keeping `scrollback_limit` lines of history
+    pub fn new(scrollback_limit: usize) -> Result<Self> {
         let pty_system = native_pty_system();
         
         // Get default shell from environment or use /bin/sh
         let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
         
         let pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
+            rows: INITIAL_ROWS,
+            cols: INITIAL_COLS,
             pixel_width: 0,
             pixel_height: 0,
         })?;
 
-        let cmd = CommandBuilder::new(&shell);
+        let hook = prompt_hook(&shell, std::env::var("HISTCONTROL").ok().as_deref());
+        let mut cmd = CommandBuilder::new(&shell);
+        cmd.env("TERM", TERM);
         let _child = pair.slave.spawn_command(cmd)?;
         
         let mut reader = pair.master.try_clone_reader()?;
-        let writer = pair.master.take_writer()?;
+        let writer = Arc::new(Mutex::new(pair.master.take_writer()?));
         
-        let output_buffer = Arc::new(Mutex::new(Vec::new()));
-        let cached_output = Arc::new(Mutex::new(String::new()));
-        let buffer_clone = Arc::clone(&output_buffer);
-        let cache_clone = Arc::clone(&cached_output);
+        let screen = new_screen(scrollback_limit);
+        let screen_clone = Arc::clone(&screen);
+        let writer_clone = Arc::clone(&writer);
         
         // Spawn thread to read from PTY
         std::thread::spawn(move || {
@@ -55,15 +119,17 @@ impl LocalShell {
             loop {
                 match reader.read(&mut buf) {
                     Ok(n) if n > 0 => {
-                        if let Ok(mut buffer) = buffer_clone.lock() {
-                            buffer.extend_from_slice(&buf[..n]);
-                            // Keep buffer from growing too large
-                            if buffer.len() > 100_000 {
-                                buffer.drain(..50_000);
+                        let responses = match screen_clone.lock() {
+                            Ok(mut screen) => {
+                                screen.process(&buf[..n]);
+                                screen.take_responses()
                             }
-                            // Update cached string
-                            if let Ok(mut cache) = cache_clone.lock() {
-                                *cache = String::from_utf8_lossy(&buffer).to_string();
+                            Err(_) => break,
+                        };
+                        if !responses.is_empty() {
+                            if let Ok(mut writer) = writer_clone.lock() {
+                                let _ = writer.write_all(&responses);
+                                let _ = writer.flush();
                             }
                         }
                     }
@@ -73,37 +139,26 @@ impl LocalShell {
             }
         });
 
-        Ok(Self {
+        let mut shell = Self {
             master: pair.master,
             writer,
-            output_buffer,
-            cached_output,
-        })
+            screen,
+            dir_sync: DirectorySync::default(),
+        };
+        if let Some(hook) = hook {
+            shell.write_input(hook.as_bytes())?;
+        }
+        Ok(shell)
     }
 
     pub fn write_input(&mut self, data: &[u8]) -> Result<()> {
-        self.writer.write_all(data)?;
-        self.writer.flush()?;
+        input_sent(&self.screen);
+        let mut writer = self.writer.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+        writer.write_all(data)?;
+        writer.flush()?;
         Ok(())
     }
 
-    pub fn clear_output(&mut self) {
-        if let Ok(mut buffer) = self.output_buffer.lock() {
-            buffer.clear();
-        }
-        if let Ok(mut cache) = self.cached_output.lock() {
-            cache.clear();
-        }
-    }
-
-    pub fn get_output(&self) -> String {
-        if let Ok(cache) = self.cached_output.lock() {
-            cache.clone()
-        } else {
-            String::new()
-        }
-    }
-
     pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
         self.master.resize(PtySize {
             rows,
@@ -111,59 +166,190 @@ impl LocalShell {
             pixel_width: 0,
             pixel_height: 0,
         })?;
+        if let Ok(mut screen) = self.screen.lock() {
+            screen.resize(rows as usize, cols as usize);
+        }
         Ok(())
     }
 }
 
 impl RemoteShell {
-    pub fn new(session: &ssh2::Session) -> Result<Self> {
+    /// Start a shell on `session`, which must be used by nothing else: it is
+    /// switched to non-blocking mode so the reader thread never holds it
+    /// while waiting for output.
+    pub fn new(session: Session, scrollback_limit: usize) -> Result<Self> {
+        let hook = remote_login_shell(&session)
+            .and_then(|(shell, histcontrol)| prompt_hook(&shell, Some(&histcontrol)));
+
         let mut channel = session.channel_session()?;
-        channel.request_pty("xterm", None, None)?;
+        channel.request_pty(
+            TERM,
+            None,
+            Some((INITIAL_COLS as u32, INITIAL_ROWS as u32, 0, 0)),
+        )?;
         channel.shell()?;
-        
-        let output_buffer = Arc::new(Mutex::new(Vec::new()));
-        
-        // TODO: Implement background thread for reading
-        // For now, we'll skip reading to avoid blocking issues
-        
-        Ok(Self {
+        session.set_blocking(false);
+
+        let channel = Arc::new(Mutex::new(channel));
+        let screen = new_screen(scrollback_limit);
+        let stop = Arc::new(AtomicBool::new(false));
+        let channel_clone = Arc::clone(&channel);
+        let screen_clone = Arc::clone(&screen);
+        let stop_clone = Arc::clone(&stop);
+
+        // Spawn thread to read from the channel. Reads never block, so the
+        // channel lock is only held briefly and writes get through.
+        let reader_thread = std::thread::spawn(move || {
+            let mut buf = [0u8; BUFFER_SIZE];
+            while !stop_clone.load(Ordering::Relaxed) {
+                let Ok(mut channel) = channel_clone.lock() else {
+                    break;
+                };
+                let result = channel.read(&mut buf);
+                let eof = channel.eof();
+                drop(channel);
+
+                match result {
+                    Ok(n) if n > 0 => {
+                        let responses = match screen_clone.lock() {
+                            Ok(mut screen) => {
+                                screen.process(&buf[..n]);
+                                screen.take_responses()
+                            }
+                            Err(_) => break,
+                        };
+                        if !responses.is_empty() {
+                            let _ = write_nonblocking(&channel_clone, &responses);
+                        }
+                    }
+                    Ok(_) if eof => break,
+                    Ok(_) => std::thread::sleep(READ_INTERVAL),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(READ_INTERVAL);
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut shell = Self {
             channel,
-            output_buffer,
-            _reader_thread: None,
-        })
+            screen,
+            dir_sync: DirectorySync::default(),
+            stop,
+            reader_thread: Some(reader_thread),
+        };
+        if let Some(hook) = hook {
+            shell.write_input(hook.as_bytes())?;
+        }
+        Ok(shell)
     }
 
     pub fn write_input(&mut self, data: &[u8]) -> Result<()> {
-        self.channel.write_all(data)?;
-        self.channel.flush()?;
-        Ok(())
+        input_sent(&self.screen);
+        write_nonblocking(&self.channel, data)
     }
 
-    pub fn read_available(&mut self) -> Result<()> {
-        // TODO: Implement proper non-blocking read
-        // For now, skip reading to avoid blocking the UI thread
-        // Remote shell output will be implemented with a background thread
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        loop {
+            let result = self
+                .channel
+                .lock()
+                .map_err(|e| anyhow!("Lock error: {}", e))?
+                .request_pty_size(cols as u32, rows as u32, None, None);
+            match result.map_err(std::io::Error::from) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(READ_INTERVAL),
+                Err(e) => return Err(e.into()),
+                Ok(()) => break,
+            }
+        }
+        if let Ok(mut screen) = self.screen.lock() {
+            screen.resize(rows as usize, cols as usize);
+        }
         Ok(())
     }
+}
 
-    pub fn clear_output(&mut self) {
-        if let Ok(mut buffer) = self.output_buffer.lock() {
-            buffer.clear();
+impl Drop for RemoteShell {
+    /// Stop the reader and close the channel, which hangs up the remote
+    /// shell. The session goes away with the last handle to the channel.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.reader_thread.take() {
+            let _ = thread.join();
         }
+        let Ok(mut channel) = self.channel.lock() else {
+            return;
+        };
+        for _ in 0..CLOSE_ATTEMPTS {
+            match channel.close().map_err(std::io::Error::from) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(READ_INTERVAL),
+                _ => break,
+            }
+        }
+    }
+}
+
+/// A screen of the initial size for a new shell
+fn new_screen(scrollback_limit: usize) -> Arc<Mutex<Screen>> {
+    let mut screen = Screen::new(INITIAL_ROWS as usize, INITIAL_COLS as usize);
+    screen.set_scrollback_limit(scrollback_limit);
+    Arc::new(Mutex::new(screen))
+}
+
+fn input_sent(screen: &Mutex<Screen>) {
+    if let Ok(mut screen) = screen.lock() {
+        screen.input_sent();
     }
+}
 
-    pub fn get_output(&self) -> String {
-        if let Ok(buffer) = self.output_buffer.lock() {
-            String::from_utf8_lossy(&buffer).to_string()
-        } else {
-            String::new()
-        }
+/// The prompt hook for the shell at `shell_path`, or `None` unless it is
+/// bash or zsh. A leading space keeps it out of the history when
+/// `histcontrol` (the shell's HISTCONTROL) asks for that.
+fn prompt_hook(shell_path: &str, histcontrol: Option<&str>) -> Option<String> {
+    let name = Path::new(shell_path).file_name()?.to_str()?;
+    if name != "bash" && name != "zsh" {
+        return None;
     }
+    let ignore_space = histcontrol
+        .is_some_and(|value| value.split(':').any(|v| v == "ignorespace" || v == "ignoreboth"));
+    Some(format!("{}{}", if ignore_space { " " } else { "" }, PROMPT_HOOK))
+}
 
-    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
-        self.channel.request_pty_size(cols as u32, rows as u32, None, None)?;
-        Ok(())
+/// Login shell and HISTCONTROL of the user on the other end of `session`,
+/// asked on a separate channel before the interactive shell starts
+fn remote_login_shell(session: &Session) -> Option<(String, String)> {
+    let mut channel = session.channel_session().ok()?;
+    channel.exec(r#"printf '%s\n%s\n' "$SHELL" "$HISTCONTROL""#).ok()?;
+    let mut output = String::new();
+    channel.read_to_string(&mut output).ok()?;
+    let _ = channel.wait_close();
+
+    let mut lines = output.lines();
+    let shell = lines.next()?.to_string();
+    Some((shell, lines.next().unwrap_or_default().to_string()))
+}
+
+/// Quote a path for a POSIX shell command line
+fn shell_quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', "'\\''"))
+}
+
+/// Write all of `data` to a channel on a non-blocking session
+fn write_nonblocking(channel: &Mutex<Channel>, data: &[u8]) -> Result<()> {
+    let mut remaining = data;
+    while !remaining.is_empty() {
+        let written = channel
+            .lock()
+            .map_err(|e| anyhow!("Lock error: {}", e))?
+            .write(remaining);
+        match written {
+            Ok(n) => remaining = &remaining[n..],
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(READ_INTERVAL),
+            Err(e) => return Err(e.into()),
+        }
     }
+    Ok(())
 }
 
 impl ShellType {
@@ -174,17 +360,35 @@ impl ShellType {
         }
     }
 
-    pub fn get_output(&self) -> String {
+    /// The terminal state, updated by the reader thread as output arrives
+    pub fn screen(&self) -> &Arc<Mutex<Screen>> {
         match self {
-            ShellType::Local(shell) => shell.get_output(),
-            ShellType::Remote(shell) => shell.get_output(),
+            ShellType::Local(shell) => &shell.screen,
+            ShellType::Remote(shell) => &shell.screen,
         }
     }
 
-    pub fn clear_output(&mut self) {
-        match self {
-            ShellType::Local(shell) => shell.clear_output(),
-            ShellType::Remote(shell) => shell.clear_output(),
+    /// Keep the shell in the panel's directory: `cd` the shell when the panel
+    /// moved and the shell is idle, or return the directory the shell moved
+    /// to so the panel can follow it
+    pub fn sync_directory(&mut self, panel_dir: &Path) -> Result<Option<PathBuf>> {
+        let (screen, dir_sync) = match self {
+            ShellType::Local(shell) => (&shell.screen, &mut shell.dir_sync),
+            ShellType::Remote(shell) => (&shell.screen, &mut shell.dir_sync),
+        };
+        let action = {
+            let screen = screen.lock().map_err(|e| anyhow!("Lock error: {}", e))?;
+            dir_sync.update(screen.cwd(), screen.at_prompt(), &panel_dir.to_string_lossy())
+        };
+
+        match action {
+            Some(SyncAction::FollowShell(dir)) => Ok(Some(PathBuf::from(dir))),
+            Some(SyncAction::ChangeShell(dir)) => {
+                // The leading space keeps the command out of the shell history
+                self.write_input(format!(" cd -- {}\n", shell_quote(&dir)).as_bytes())?;
+                Ok(None)
+            }
+            None => Ok(None),
         }
     }
 
@@ -195,3 +399,69 @@ impl ShellType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_hook_only_for_bash_and_zsh() {
+        assert!(prompt_hook("/bin/bash", None).unwrap().starts_with("__rc_cwd"));
+        assert!(prompt_hook("/usr/bin/zsh", Some("")).is_some());
+        assert!(prompt_hook("/usr/bin/fish", None).is_none());
+        assert!(prompt_hook("/bin/tcsh", None).is_none());
+        assert!(prompt_hook("", None).is_none());
+
+        let hidden = prompt_hook("/bin/bash", Some("erasedups:ignoreboth")).unwrap();
+        assert!(hidden.starts_with(" __rc_cwd"));
+        assert!(!hidden.contains("clear"));
+    }
+
+    #[test]
+    fn test_prompt_hook_reports_escaped_path() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("a%41\x07b");
+        std::fs::create_dir(&dir).unwrap();
+
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(format!("{}__rc_cwd", PROMPT_HOOK))
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        let mut screen = Screen::new(4, 20);
+        screen.process(&output.stdout);
+        assert_eq!(screen.cwd(), dir.to_str());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(shell_quote("/tmp/it's"), "'/tmp/it'\\''s'");
+    }
+
+    #[test]
+    fn test_directory_sync() {
+        let mut sync = DirectorySync::default();
+
+        // Nothing is known until the shell reports its directory
+        assert_eq!(sync.update(None, false, "/home"), None);
+        assert_eq!(sync.update(Some("/home"), true, "/home"), None);
+
+        // The panel moved: cd the shell, once, and only when it is idle
+        assert_eq!(sync.update(Some("/home"), false, "/tmp"), None);
+        assert_eq!(
+            sync.update(Some("/home"), true, "/tmp"),
+            Some(SyncAction::ChangeShell("/tmp".to_string()))
+        );
+        assert_eq!(sync.update(Some("/home"), true, "/tmp"), None);
+
+        // The shell moved: the panel follows
+        assert_eq!(sync.update(Some("/tmp"), true, "/tmp"), None);
+        assert_eq!(
+            sync.update(Some("/var"), true, "/tmp"),
+            Some(SyncAction::FollowShell("/var".to_string()))
+        );
+        assert_eq!(sync.update(Some("/var"), true, "/var"), None);
+    }
+}
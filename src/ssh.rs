@@ -1,68 +1,152 @@
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{Local, TimeZone};
 use ssh2::{Session, Sftp};
-use std::io::{Read, Write};
+use std::io::Read;
 use std::net::TcpStream;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use crate::filesystem::{FileEntry, FileSystem};
+use crate::known_hosts;
+use crate::ssh_config::{self, SshConfig};
+use crate::tunnel;
 
-/// Parsed SSH connection string
+/// Where and as whom to connect, after applying ~/.ssh/config
 #[derive(Debug, Clone)]
 pub struct SshConnectionInfo {
     pub username: String,
     pub hostname: String,
     pub port: u16,
+    /// Keys from IdentityFile options, tried before the default key files
+    pub identity_files: Vec<PathBuf>,
+    /// Hosts to tunnel through, in connection order
+    pub jump_hosts: Vec<SshConnectionInfo>,
 }
 
 impl SshConnectionInfo {
-    /// Parse a connection string like "user@hostname" or "user@hostname:port"
-    pub fn parse(connection_string: &str) -> Result<Self> {
-        let (user_host, port) = if connection_string.contains(':') {
-            let parts: Vec<&str> = connection_string.rsplitn(2, ':').collect();
-            let port: u16 = parts[0].parse().context("Invalid port number")?;
-            (parts[1], port)
-        } else {
-            (connection_string, 22)
+    /// Resolve a target like "alias", "user@hostname" or "user@hostname:port".
+    /// A user or port given explicitly overrides the configuration.
+    pub fn resolve(target: &str, config: &SshConfig) -> Result<Self> {
+        let (mut info, proxy_jump) = Self::resolve_host(target, config)?;
+        if let Some(spec) = proxy_jump {
+            info.jump_hosts = Self::resolve_jump_hosts(&spec, config)?;
+        }
+        Ok(info)
+    }
+
+    /// Resolve a comma-separated ProxyJump list; "none" means connect directly
+    pub fn resolve_jump_hosts(spec: &str, config: &SshConfig) -> Result<Vec<Self>> {
+        if spec.eq_ignore_ascii_case("none") {
+            return Ok(Vec::new());
+        }
+
+        // Jump hosts' own ProxyJump settings are not followed
+        spec.split(',')
+            .map(|hop| {
+                Self::resolve_host(hop.trim(), config)
+                    .map(|(info, _)| info)
+                    .with_context(|| format!("Invalid jump host '{}'", hop))
+            })
+            .collect()
+    }
+
+    /// Resolve a single host, returning its ProxyJump setting alongside
+    fn resolve_host(target: &str, config: &SshConfig) -> Result<(Self, Option<String>)> {
+        let (user_host, port) = match target.rsplit_once(':') {
+            Some((user_host, port)) => {
+                let port: u16 = port.parse().context("Invalid port number")?;
+                (user_host, Some(port))
+            }
+            None => (target, None),
         };
 
-        let parts: Vec<&str> = user_host.splitn(2, '@').collect();
-        if parts.len() != 2 {
+        let (user, host) = match user_host.split_once('@') {
+            Some((user, host)) => (Some(user), host),
+            None => (None, user_host),
+        };
+        if host.is_empty() || user == Some("") {
             return Err(anyhow!(
-                "Invalid connection string. Expected format: user@hostname[:port]"
+                "Invalid connection string. Expected format: [user@]hostname[:port]"
             ));
         }
 
-        Ok(Self {
-            username: parts[0].to_string(),
-            hostname: parts[1].to_string(),
-            port,
-        })
+        let host_config = config.lookup(host);
+        let username = user
+            .map(str::to_string)
+            .or(host_config.user)
+            .or_else(ssh_config::local_username)
+            .ok_or_else(|| anyhow!("No user name given for {}", host))?;
+        let hostname = host_config.hostname.unwrap_or_else(|| host.to_string());
+        let identity_files = host_config
+            .identity_files
+            .iter()
+            .map(|file| ssh_config::expand_path(file, &hostname, &username))
+            .collect();
+
+        let info = Self {
+            username,
+            hostname,
+            port: port.or(host_config.port).unwrap_or(22),
+            identity_files,
+            jump_hosts: Vec::new(),
+        };
+        Ok((info, host_config.proxy_jump))
     }
 }
 
 /// SSH connection manager
 pub struct SshConnection {
     pub session: Session,
-    sftp: Sftp,
-    pub info: SshConnectionInfo,
+    /// A second login used only by the remote shell, so waiting for shell
+    /// output never holds up SFTP on `session` (libssh2's blocking mode is
+    /// per session). Holds the reason if the server refused it.
+    pub shell_session: std::result::Result<Session, String>,
     pub home_dir: PathBuf,
 }
 
 impl SshConnection {
-    /// Establish an SSH connection
-    pub fn connect(info: SshConnectionInfo, password: Option<&str>) -> Result<Self> {
-        let addr = format!("{}:{}", info.hostname, info.port);
-        let tcp = TcpStream::connect(&addr)
-            .with_context(|| format!("Failed to connect to {}", addr))?;
+    /// Establish an SSH connection, asking `confirm_host` before trusting an
+    /// unknown host key
+    pub fn connect(
+        info: SshConnectionInfo,
+        password: Option<&str>,
+        confirm_host: &mut dyn FnMut(&str) -> Result<bool>,
+    ) -> Result<Self> {
+        let session = Self::login(&info, password, confirm_host)?;
+        // Fail here, not in RemoteFileSystem::new, if SFTP is unavailable
+        session.sftp().context("SFTP subsystem unavailable")?;
 
-        let mut session = Session::new()?;
-        session.set_tcp_stream(tcp);
-        session.handshake()?;
+        // Get user's home directory
+        let home_dir = Self::get_home_directory(&session, &info.username)?;
+
+        // The host key is known by now, so this does not prompt again
+        let shell_session =
+            Self::login(&info, password, confirm_host).map_err(|e| format!("{:#}", e));
+
+        Ok(Self {
+            session,
+            shell_session,
+            home_dir,
+        })
+    }
+
+    /// Open an authenticated session to the target
+    fn login(
+        info: &SshConnectionInfo,
+        password: Option<&str>,
+        confirm_host: &mut dyn FnMut(&str) -> Result<bool>,
+    ) -> Result<Session> {
+        let session = if info.jump_hosts.is_empty() {
+            Self::handshake(Self::connect_tcp(info)?, info, confirm_host)?
+        } else {
+            let tunnel = Self::connect_through_jump_hosts(info, confirm_host)?;
+            Self::handshake(tunnel, info, confirm_host)?
+        };
 
         // Try SSH key authentication first
-        let auth_success = Self::try_key_auth(&session, &info.username)
+        let auth_success = Self::try_key_auth(&session, info)
             .unwrap_or(false);
 
         if !auth_success {
@@ -82,21 +166,75 @@ impl SshConnection {
             return Err(anyhow!("Authentication failed"));
         }
 
-        let sftp = session.sftp()?;
-        
-        // Get user's home directory
-        let home_dir = Self::get_home_directory(&session, &info.username)?;
+        Ok(session)
+    }
 
-        Ok(Self {
-            session,
-            sftp,
-            info,
-            home_dir,
-        })
+    fn connect_tcp(info: &SshConnectionInfo) -> Result<TcpStream> {
+        let addr = format!("{}:{}", info.hostname, info.port);
+        TcpStream::connect(&addr).with_context(|| format!("Failed to connect to {}", addr))
+    }
+
+    /// Start a session over `stream` and check the server's host key
+    fn handshake<S: AsRawFd + 'static>(
+        stream: S,
+        info: &SshConnectionInfo,
+        confirm_host: &mut dyn FnMut(&str) -> Result<bool>,
+    ) -> Result<Session> {
+        let mut session = Session::new()?;
+        session.set_tcp_stream(stream);
+        session
+            .handshake()
+            .with_context(|| format!("SSH handshake with {} failed", info.hostname))?;
+
+        // Refuse to send credentials to a server we cannot identify
+        known_hosts::verify_host_key(&session, &info.hostname, info.port, confirm_host)?;
+
+        Ok(session)
+    }
+
+    /// Log in to each jump host in turn, each one reached through the
+    /// previous, and return a stream to the final target
+    fn connect_through_jump_hosts(
+        info: &SshConnectionInfo,
+        confirm_host: &mut dyn FnMut(&str) -> Result<bool>,
+    ) -> Result<UnixStream> {
+        let mut stream: Option<UnixStream> = None;
+
+        for (index, hop) in info.jump_hosts.iter().enumerate() {
+            let session = match stream.take() {
+                Some(tunnel) => Self::handshake(tunnel, hop, confirm_host)?,
+                None => Self::handshake(Self::connect_tcp(hop)?, hop, confirm_host)?,
+            };
+
+            // Jump hosts only get key authentication; the password is for the target
+            if !Self::try_key_auth(&session, hop).unwrap_or(false) {
+                return Err(anyhow!(
+                    "SSH key authentication to jump host {}@{} failed",
+                    hop.username,
+                    hop.hostname
+                ));
+            }
+
+            let next = info.jump_hosts.get(index + 1).unwrap_or(info);
+            let channel = session
+                .channel_direct_tcpip(&next.hostname, next.port, None)
+                .with_context(|| {
+                    format!(
+                        "Jump host {} could not open a connection to {}:{}",
+                        hop.hostname, next.hostname, next.port
+                    )
+                })?;
+
+            stream = Some(tunnel::forward(session, channel)?);
+        }
+
+        stream.ok_or_else(|| anyhow!("No jump hosts given"))
     }
 
     /// Try to authenticate using SSH keys
-    fn try_key_auth(session: &Session, username: &str) -> Result<bool> {
+    fn try_key_auth(session: &Session, info: &SshConnectionInfo) -> Result<bool> {
+        let username = info.username.as_str();
+
         // Try SSH agent first
         if let Ok(mut agent) = session.agent() {
             if agent.connect().is_ok() {
@@ -109,20 +247,27 @@ impl SshConnection {
             }
         }
 
-        // Try default key locations
+        // Try configured keys, then default key locations
         let home = dirs::home_dir().ok_or_else(|| anyhow!("Cannot find home directory"))?;
         let ssh_dir = home.join(".ssh");
 
         let key_files = ["id_ed25519", "id_rsa", "id_ecdsa", "id_dsa"];
-        
-        for key_name in &key_files {
-            let private_key = ssh_dir.join(key_name);
-            let public_key = ssh_dir.join(format!("{}.pub", key_name));
+        let private_keys = info
+            .identity_files
+            .iter()
+            .cloned()
+            .chain(key_files.iter().map(|key_name| ssh_dir.join(key_name)));
+
+        for private_key in private_keys {
+            let mut public_key = private_key.clone().into_os_string();
+            public_key.push(".pub");
+            let public_key = PathBuf::from(public_key);
 
             if private_key.exists() {
                 // Try without passphrase first
+                let public_key = public_key.exists().then_some(public_key.as_path());
                 if session
-                    .userauth_pubkey_file(username, Some(&public_key), &private_key, None)
+                    .userauth_pubkey_file(username, public_key, &private_key, None)
                     .is_ok()
                 {
                     return Ok(true);
@@ -151,15 +296,12 @@ impl SshConnection {
         }
     }
 
-    /// Get the SFTP handle
-    pub fn sftp(&self) -> &Sftp {
-        &self.sftp
-    }
 }
 
 /// Remote file system implementation using SFTP
 pub struct RemoteFileSystem {
     sftp: Arc<Mutex<Sftp>>,
+    home_dir: PathBuf,
 }
 
 impl RemoteFileSystem {
@@ -168,13 +310,10 @@ impl RemoteFileSystem {
         // So we'll use Arc<Mutex> for thread safety
         Self {
             sftp: Arc::new(Mutex::new(connection.session.sftp().unwrap())),
+            home_dir: connection.home_dir.clone(),
         }
     }
 
-    pub fn from_sftp(sftp: Arc<Mutex<Sftp>>) -> Self {
-        Self { sftp }
-    }
-
     pub fn sftp_handle(&self) -> Arc<Mutex<Sftp>> {
         self.sftp.clone()
     }
@@ -194,7 +333,6 @@ impl FileSystem for RemoteFileSystem {
                 is_dir: true,
                 size: 0,
                 modified: None,
-                permissions: 0o755,
             });
         }
 
@@ -217,7 +355,6 @@ impl FileSystem for RemoteFileSystem {
             let modified = stat.mtime.map(|t| {
                 Local.timestamp_opt(t as i64, 0).single().unwrap_or_else(Local::now)
             });
-            let permissions = stat.perm.unwrap_or(0) & 0o777;
 
             entries.push(FileEntry {
                 name,
@@ -225,25 +362,9 @@ impl FileSystem for RemoteFileSystem {
                 is_dir,
                 size,
                 modified,
-                permissions,
             });
         }
 
-        // Sort: directories first, then by name
-        entries.sort_by(|a, b| {
-            if a.name == ".." {
-                std::cmp::Ordering::Less
-            } else if b.name == ".." {
-                std::cmp::Ordering::Greater
-            } else if a.is_dir && !b.is_dir {
-                std::cmp::Ordering::Less
-            } else if !a.is_dir && b.is_dir {
-                std::cmp::Ordering::Greater
-            } else {
-                a.name.to_lowercase().cmp(&b.name.to_lowercase())
-            }
-        });
-
         Ok(entries)
     }
 
@@ -266,6 +387,10 @@ impl FileSystem for RemoteFileSystem {
         
         sftp.stat(path).is_ok()
     }
+
+    fn home_dir(&self) -> PathBuf {
+        self.home_dir.clone()
+    }
 }
 
 #[cfg(test)]
@@ -274,7 +399,7 @@ mod tests {
 
     #[test]
     fn test_parse_connection_string_simple() {
-        let info = SshConnectionInfo::parse("user@hostname").unwrap();
+        let info = SshConnectionInfo::resolve("user@hostname", &SshConfig::default()).unwrap();
         assert_eq!(info.username, "user");
         assert_eq!(info.hostname, "hostname");
         assert_eq!(info.port, 22);
@@ -282,7 +407,7 @@ mod tests {
 
     #[test]
     fn test_parse_connection_string_with_port() {
-        let info = SshConnectionInfo::parse("admin@server.com:2222").unwrap();
+        let info = SshConnectionInfo::resolve("admin@server.com:2222", &SshConfig::default()).unwrap();
         assert_eq!(info.username, "admin");
         assert_eq!(info.hostname, "server.com");
         assert_eq!(info.port, 2222);
@@ -290,13 +415,62 @@ mod tests {
 
     #[test]
     fn test_parse_connection_string_invalid() {
-        let result = SshConnectionInfo::parse("hostname");
+        let result = SshConnectionInfo::resolve("user@", &SshConfig::default());
         assert!(result.is_err());
     }
 
     #[test]
     fn test_parse_connection_string_invalid_port() {
-        let result = SshConnectionInfo::parse("user@host:notaport");
+        let result = SshConnectionInfo::resolve("user@host:notaport", &SshConfig::default());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_resolve_config_alias() {
+        let config = SshConfig::parse(
+            "Host prod-db
+               HostName db1.example.com
+               User postgres
+               Port 2200
+               IdentityFile /keys/%h",
+        )
+        .unwrap();
+
+        let info = SshConnectionInfo::resolve("prod-db", &config).unwrap();
+        assert_eq!(info.username, "postgres");
+        assert_eq!(info.hostname, "db1.example.com");
+        assert_eq!(info.port, 2200);
+        assert_eq!(info.identity_files, [PathBuf::from("/keys/db1.example.com")]);
+
+        // Explicit user and port win over the configuration
+        let info = SshConnectionInfo::resolve("admin@prod-db:22", &config).unwrap();
+        assert_eq!(info.username, "admin");
+        assert_eq!(info.port, 22);
+    }
+
+    #[test]
+    fn test_resolve_proxy_jump_chain() {
+        let config = SshConfig::parse(
+            "Host db
+               ProxyJump ops@bastion:2222,inner
+             Host inner
+               HostName 10.0.0.5
+               User ops
+               ProxyJump loop",
+        )
+        .unwrap();
+
+        let info = SshConnectionInfo::resolve("admin@db", &config).unwrap();
+        let hops: Vec<_> = info
+            .jump_hosts
+            .iter()
+            .map(|hop| (hop.hostname.as_str(), hop.port))
+            .collect();
+        assert_eq!(hops, [("bastion", 2222), ("10.0.0.5", 22)]);
+        assert_eq!(info.jump_hosts[0].username, "ops");
+        assert!(info.jump_hosts[1].jump_hosts.is_empty());
+
+        let direct = SshConnectionInfo::resolve_jump_hosts("none", &config).unwrap();
+        assert!(direct.is_empty());
+    }
 }
@@ -0,0 +1,264 @@
+//! OpenSSH client configuration (~/.ssh/config)
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::filesystem::glob_match;
+
+/// Options that apply to one host, merged from every matching block
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostConfig {
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_files: Vec<String>,
+    /// Comma-separated jump hosts, or "none"
+    pub proxy_jump: Option<String>,
+}
+
+/// A `Host` block: its patterns and options in file order
+#[derive(Debug, Clone)]
+struct HostBlock {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl HostBlock {
+    /// True if a pattern matches and no negated (`!`) pattern does
+    fn matches(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let mut matched = false;
+        for pattern in &self.patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if glob_match(negated, &host) => return false,
+                Some(_) => {}
+                None => matched |= glob_match(pattern, &host),
+            }
+        }
+        matched
+    }
+}
+
+/// Parsed client configuration
+#[derive(Debug, Clone, Default)]
+pub struct SshConfig {
+    blocks: Vec<HostBlock>,
+}
+
+impl SshConfig {
+    /// Load ~/.ssh/config, or an empty configuration if there is none
+    pub fn load() -> Result<Self> {
+        let path = match dirs::home_dir() {
+            Some(home) => home.join(".ssh").join("config"),
+            None => return Ok(Self::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents)
+                .with_context(|| format!("Bad configuration in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        // Options before the first Host line apply to every host
+        let mut blocks = vec![HostBlock {
+            patterns: vec!["*".to_string()],
+            options: Vec::new(),
+        }];
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, value) = split_option(line);
+            let keyword = keyword.to_lowercase();
+            if value.is_empty() {
+                return Err(anyhow!("Missing value for {} on line {}", keyword, number + 1));
+            }
+
+            match keyword.as_str() {
+                "host" => blocks.push(HostBlock {
+                    patterns: value.split_whitespace().map(|p| p.to_lowercase()).collect(),
+                    options: Vec::new(),
+                }),
+                // Match criteria are not supported; such blocks never apply
+                "match" => blocks.push(HostBlock {
+                    patterns: Vec::new(),
+                    options: Vec::new(),
+                }),
+                "port" if value.parse::<u16>().is_err() => {
+                    return Err(anyhow!("Bad port '{}' on line {}", value, number + 1));
+                }
+                _ => {
+                    if let Some(block) = blocks.last_mut() {
+                        block.options.push((keyword, unquote(value).to_string()));
+                    }
+                }
+            }
+        }
+
+        Ok(Self { blocks })
+    }
+
+    /// Options for `host`; like OpenSSH, the first value found for an option wins
+    pub fn lookup(&self, host: &str) -> HostConfig {
+        let mut config = HostConfig::default();
+
+        for block in self.blocks.iter().filter(|b| b.matches(host)) {
+            for (keyword, value) in &block.options {
+                match keyword.as_str() {
+                    "hostname" if config.hostname.is_none() => {
+                        config.hostname = Some(value.replace("%h", host));
+                    }
+                    "user" if config.user.is_none() => config.user = Some(value.clone()),
+                    "port" if config.port.is_none() => config.port = value.parse().ok(),
+                    "identityfile" => config.identity_files.push(value.clone()),
+                    "proxyjump" if config.proxy_jump.is_none() => {
+                        config.proxy_jump = Some(value.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        config
+    }
+}
+
+/// Split "Keyword value" or "Keyword=value"
+fn split_option(line: &str) -> (&str, &str) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+    (keyword, rest.trim())
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Expand `~` and the %d, %h, %r, %u tokens in an IdentityFile path
+pub fn expand_path(value: &str, hostname: &str, remote_user: &str) -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_default();
+    let local_user = local_username().unwrap_or_default();
+
+    let value = match value.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", home.display(), rest),
+        None => value.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('d') => expanded.push_str(&home.display().to_string()),
+            Some('h') => expanded.push_str(hostname),
+            Some('r') => expanded.push_str(remote_user),
+            Some('u') => expanded.push_str(&local_user),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+
+    PathBuf::from(expanded)
+}
+
+/// Name of the local user, the default remote user like in OpenSSH
+pub fn local_username() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+# Global defaults
+IdentityFile ~/.ssh/id_global
+
+Host prod-db
+    HostName db1.internal.example.com
+    ProxyJump bastion
+    User postgres
+    Port 2200
+    IdentityFile ~/.ssh/id_prod
+
+Host *.example.com !bastion.example.com
+    User deploy
+
+Host=web?
+    HostName=\"%h.example.com\"
+
+Host *
+    User fallback
+    Port 22
+";
+
+    #[test]
+    fn test_alias_resolves_options() {
+        let config = SshConfig::parse(CONFIG).unwrap();
+        let host = config.lookup("prod-db");
+
+        assert_eq!(host.hostname.as_deref(), Some("db1.internal.example.com"));
+        assert_eq!(host.user.as_deref(), Some("postgres"));
+        assert_eq!(host.port, Some(2200));
+        assert_eq!(host.identity_files, ["~/.ssh/id_global", "~/.ssh/id_prod"]);
+        assert_eq!(host.proxy_jump.as_deref(), Some("bastion"));
+    }
+
+    #[test]
+    fn test_wildcards_and_negation() {
+        let config = SshConfig::parse(CONFIG).unwrap();
+
+        assert_eq!(config.lookup("app.example.com").user.as_deref(), Some("deploy"));
+        assert_eq!(config.lookup("bastion.example.com").user.as_deref(), Some("fallback"));
+        assert_eq!(config.lookup("other").user.as_deref(), Some("fallback"));
+        assert_eq!(config.lookup("other").hostname, None);
+    }
+
+    #[test]
+    fn test_hostname_token_and_equals_syntax() {
+        let config = SshConfig::parse(CONFIG).unwrap();
+        let host = config.lookup("web1");
+
+        assert_eq!(host.hostname.as_deref(), Some("web1.example.com"));
+        assert_eq!(host.port, Some(22));
+    }
+
+    #[test]
+    fn test_bad_port_is_rejected() {
+        assert!(SshConfig::parse("Host x\n  Port ssh\n").is_err());
+    }
+
+    #[test]
+    fn test_expand_path() {
+        assert_eq!(
+            expand_path("/keys/%r@%h%%", "db1", "postgres"),
+            PathBuf::from("/keys/postgres@db1%")
+        );
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path("~/.ssh/id_prod", "h", "u"), home.join(".ssh/id_prod"));
+    }
+}
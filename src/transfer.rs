@@ -1,89 +1,493 @@
 //! File transfer operations between local and remote filesystems
 
 use anyhow::{Context, Result};
+use ssh2::{OpenFlags, OpenType, Sftp};
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 use crate::file_panel::FilePanel;
-use crate::ssh::RemoteFileSystem;
 
 /// Buffer size for file transfers (64KB)
 const BUFFER_SIZE: usize = 64 * 1024;
 
-/// Transfer a file from the source panel to the destination panel
-pub fn copy_file(
-    source_panel: &FilePanel,
-    dest_panel: &FilePanel,
-    source_path: &Path,
-    dest_path: &Path,
-) -> Result<u64> {
-    // Determine the transfer type based on filesystem types
-    let source_is_remote = source_panel.is_remote();
-    let dest_is_remote = dest_panel.is_remote();
+/// How much of a partial destination is compared before resuming (1MB)
+const RESUME_VERIFY_SIZE: u64 = 1024 * 1024;
+
+/// Whether a copy starts from scratch or continues a partial destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMode {
+    Overwrite,
+    Resume,
+    /// Resume after checking that the tail of the partial file matches the source
+    ResumeVerified,
+}
+
+/// Totals for a completed transfer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Live counters for a running transfer.
+///
+/// Shared between the worker thread doing the copy and the UI thread drawing
+/// the progress dialog. Cancelling makes the copy loop stop at the next buffer.
+#[derive(Debug, Default)]
+pub struct TransferProgress {
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    files_done: AtomicU64,
+    files_total: AtomicU64,
+    current_file: Mutex<PathBuf>,
+    started: Mutex<Option<Instant>>,
+    cancelled: AtomicBool,
+}
+
+impl TransferProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes_done(&self) -> u64 {
+        self.bytes_done.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_total(&self) -> u64 {
+        self.bytes_total.load(Ordering::Relaxed)
+    }
+
+    pub fn files_done(&self) -> u64 {
+        self.files_done.load(Ordering::Relaxed)
+    }
+
+    pub fn files_total(&self) -> u64 {
+        self.files_total.load(Ordering::Relaxed)
+    }
+
+    pub fn current_file(&self) -> PathBuf {
+        self.current_file
+            .lock()
+            .map(|path| path.clone())
+            .unwrap_or_default()
+    }
 
-    match (source_is_remote, dest_is_remote) {
-        (false, false) => copy_local_to_local(source_path, dest_path),
-        (false, true) => copy_local_to_remote(source_path, dest_path, dest_panel),
-        (true, false) => copy_remote_to_local(source_path, dest_path, source_panel),
-        (true, true) => copy_remote_to_remote(source_path, dest_path, source_panel, dest_panel),
+    /// Fraction of bytes transferred, between 0.0 and 1.0
+    pub fn ratio(&self) -> f64 {
+        let total = self.bytes_total();
+        if total == 0 {
+            0.0
+        } else {
+            (self.bytes_done() as f64 / total as f64).min(1.0)
+        }
+    }
+
+    /// Average throughput in bytes per second since the transfer started
+    pub fn throughput(&self) -> Option<f64> {
+        let started = (*self.started.lock().ok()?)?;
+        let elapsed = started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            Some(self.bytes_done() as f64 / elapsed)
+        } else {
+            None
+        }
+    }
+
+    /// Estimated time left at the current average throughput
+    pub fn eta(&self) -> Option<Duration> {
+        let throughput = self.throughput().filter(|rate| *rate > 0.0)?;
+        let remaining = self.bytes_total().saturating_sub(self.bytes_done());
+        Some(Duration::from_secs_f64(remaining as f64 / throughput))
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn start(&self, files_total: u64, bytes_total: u64) {
+        self.files_total.store(files_total, Ordering::Relaxed);
+        self.bytes_total.store(bytes_total, Ordering::Relaxed);
+        if let Ok(mut started) = self.started.lock() {
+            *started = Some(Instant::now());
+        }
+    }
+
+    fn set_current_file(&self, path: &Path) {
+        if let Ok(mut current) = self.current_file.lock() {
+            *current = path.to_path_buf();
+        }
+    }
+
+    fn add_bytes(&self, bytes: u64) {
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn finish_file(&self) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
     }
 }
 
-/// Copy a file locally
-fn copy_local_to_local(source: &Path, dest: &Path) -> Result<u64> {
-    fs::copy(source, dest).with_context(|| {
-        format!(
-            "Failed to copy {} to {}",
-            source.display(),
-            dest.display()
-        )
-    })
+/// One side of a transfer: the local disk or a remote SFTP session.
+///
+/// Unlike a `FilePanel` this can be sent to a worker thread.
+#[derive(Clone)]
+pub enum Endpoint {
+    Local,
+    Remote(Arc<Mutex<Sftp>>),
 }
 
-/// Copy a local file to a remote destination
-fn copy_local_to_remote(source: &Path, dest: &Path, dest_panel: &FilePanel) -> Result<u64> {
-    let sftp = dest_panel
-        .get_sftp()
-        .context("Destination is not a remote filesystem")?;
+impl Endpoint {
+    /// The filesystem a panel is browsing
+    pub fn of(panel: &FilePanel) -> Self {
+        match panel.get_sftp() {
+            Some(sftp) => Endpoint::Remote(sftp),
+            None => Endpoint::Local,
+        }
+    }
 
-    let sftp_guard = sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+    /// Whether both endpoints are the same filesystem, so the same path on
+    /// each is the same file
+    pub fn same_filesystem(&self, other: &Endpoint) -> bool {
+        match (self, other) {
+            (Endpoint::Local, Endpoint::Local) => true,
+            (Endpoint::Remote(a), Endpoint::Remote(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
 
-    // Read local file
-    let mut local_file = fs::File::open(source)
-        .with_context(|| format!("Failed to open local file: {}", source.display()))?;
+/// Refuse to copy or move a path onto itself or into its own subdirectory
+fn check_not_into_itself(verb: &str, source_path: &Path, dest_path: &Path) -> Result<()> {
+    if source_path == dest_path {
+        anyhow::bail!("Source and destination are the same: {}", source_path.display());
+    }
+    if dest_path.starts_with(source_path) {
+        anyhow::bail!("Cannot {} a directory into itself: {}", verb, source_path.display());
+    }
+    Ok(())
+}
 
-    // Create remote file
-    let mut remote_file = sftp_guard
-        .create(dest)
-        .with_context(|| format!("Failed to create remote file: {}", dest.display()))?;
+pub fn lock_sftp(sftp: &Mutex<Sftp>) -> Result<MutexGuard<'_, Sftp>> {
+    sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))
+}
+
+/// A single entry of a source tree, relative to the tree root
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    relative: PathBuf,
+    is_dir: bool,
+    size: u64,
+}
+
+/// One top-level file or directory of a transfer
+#[derive(Debug, Clone)]
+pub struct TransferItem {
+    pub source_path: PathBuf,
+    pub dest_path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Transfer files or whole directory trees between two endpoints.
+///
+/// All source trees are walked up front so `progress` knows the totals. It is
+/// updated as data is copied; once it is cancelled the copy stops with an
+/// error and the partially written file is removed.
+pub fn copy_files(
+    source: &Endpoint,
+    dest: &Endpoint,
+    items: &[TransferItem],
+    progress: &TransferProgress,
+) -> Result<TransferStats> {
+    let mut trees = Vec::with_capacity(items.len());
+
+    for item in items {
+        if source.same_filesystem(dest) {
+            check_not_into_itself("copy", &item.source_path, &item.dest_path)?;
+        }
+        trees.push(collect_tree(source, &item.source_path)?);
+    }
+
+    let all_entries = trees.iter().flatten();
+    let files_total = all_entries.clone().filter(|entry| !entry.is_dir).count() as u64;
+    let bytes_total = all_entries.map(|entry| entry.size).sum();
+    progress.start(files_total, bytes_total);
+
+    let mut stats = TransferStats::default();
+
+    for (item, tree) in items.iter().zip(&trees) {
+        for entry in tree {
+            let source_file = join_relative(&item.source_path, &entry.relative);
+            let dest_file = join_relative(&item.dest_path, &entry.relative);
+
+            if entry.is_dir {
+                ensure_directory(dest, &dest_file)?;
+                continue;
+            }
+
+            progress.set_current_file(&source_file);
+            match copy_single_file(source, dest, &source_file, &dest_file, progress) {
+                Ok(bytes) => {
+                    stats.bytes += bytes;
+                    stats.files += 1;
+                    progress.finish_file();
+                }
+                Err(e) => {
+                    if progress.is_cancelled() {
+                        let _ = delete_file(dest, &dest_file);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Join a tree-relative path onto a base, keeping the base itself for the tree root
+fn join_relative(base: &Path, relative: &Path) -> PathBuf {
+    if relative.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(relative)
+    }
+}
 
-    // Transfer data
+/// Walk the source tree and return its entries with parents before children.
+///
+/// The root is resolved through symlinks. Below the root, symlinks to files are
+/// copied as regular files while symlinked directories are not descended into,
+/// which keeps link cycles from recursing forever.
+fn collect_tree(endpoint: &Endpoint, root: &Path) -> Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+
+    match endpoint {
+        Endpoint::Remote(sftp) => {
+            let sftp_guard = lock_sftp(sftp)?;
+            let stat = sftp_guard
+                .stat(root)
+                .with_context(|| format!("Failed to stat remote path: {}", root.display()))?;
+            entries.push(TreeEntry {
+                relative: PathBuf::new(),
+                is_dir: stat.is_dir(),
+                size: if stat.is_dir() { 0 } else { stat.size.unwrap_or(0) },
+            });
+            if stat.is_dir() {
+                collect_remote_children(&sftp_guard, root, Path::new(""), &mut entries)?;
+            }
+        }
+        Endpoint::Local => {
+            let metadata = fs::metadata(root)
+                .with_context(|| format!("Failed to stat local path: {}", root.display()))?;
+            entries.push(TreeEntry {
+                relative: PathBuf::new(),
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+            });
+            if metadata.is_dir() {
+                collect_local_children(root, Path::new(""), &mut entries)?;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn collect_local_children(root: &Path, relative: &Path, entries: &mut Vec<TreeEntry>) -> Result<()> {
+    let dir = join_relative(root, relative);
+    let read_dir = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read local directory: {}", dir.display()))?;
+
+    for entry in read_dir {
+        let entry = entry?;
+        let child = relative.join(entry.file_name());
+
+        // Follows symlinks, so a link to a file reports the target's size
+        let metadata = match fs::metadata(entry.path()) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if entry.file_type()?.is_symlink() && !metadata.is_file() {
+            continue;
+        }
+
+        let is_dir = metadata.is_dir();
+        entries.push(TreeEntry {
+            relative: child.clone(),
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+        });
+        if is_dir {
+            collect_local_children(root, &child, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_remote_children(
+    sftp: &Sftp,
+    root: &Path,
+    relative: &Path,
+    entries: &mut Vec<TreeEntry>,
+) -> Result<()> {
+    let dir = join_relative(root, relative);
+    let dir_entries = sftp
+        .readdir(&dir)
+        .with_context(|| format!("Failed to read remote directory: {}", dir.display()))?;
+
+    for (path, mut stat) in dir_entries {
+        let name = match path.file_name() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let child = relative.join(name);
+
+        if stat.file_type().is_symlink() {
+            match sftp.stat(&path) {
+                Ok(target) if target.is_file() => stat = target,
+                _ => continue,
+            }
+        }
+
+        let is_dir = stat.is_dir();
+        entries.push(TreeEntry {
+            relative: child.clone(),
+            is_dir,
+            size: if is_dir { 0 } else { stat.size.unwrap_or(0) },
+        });
+        if is_dir {
+            collect_remote_children(sftp, root, &child, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a directory on the destination unless it already exists
+fn ensure_directory(endpoint: &Endpoint, path: &Path) -> Result<()> {
+    let exists = match endpoint {
+        Endpoint::Remote(sftp) => lock_sftp(sftp)?
+            .stat(path)
+            .map(|stat| stat.is_dir())
+            .unwrap_or(false),
+        Endpoint::Local => path.is_dir(),
+    };
+    if exists {
+        Ok(())
+    } else {
+        create_directory(endpoint, path)
+    }
+}
+
+/// Copy a single regular file between the two endpoints
+fn copy_single_file(
+    source: &Endpoint,
+    dest: &Endpoint,
+    source_path: &Path,
+    dest_path: &Path,
+    progress: &TransferProgress,
+) -> Result<u64> {
+    match (source, dest) {
+        (Endpoint::Local, Endpoint::Local) => copy_local_to_local(source_path, dest_path, progress),
+        (Endpoint::Local, Endpoint::Remote(dest_sftp)) => {
+            copy_local_to_remote(source_path, dest_path, dest_sftp, progress)
+        }
+        (Endpoint::Remote(source_sftp), Endpoint::Local) => {
+            copy_remote_to_local(source_path, dest_path, source_sftp, progress)
+        }
+        (Endpoint::Remote(source_sftp), Endpoint::Remote(dest_sftp)) => {
+            copy_remote_to_remote(source_path, dest_path, source_sftp, dest_sftp, progress)
+        }
+    }
+}
+
+/// Pump data from reader to writer, reporting progress and honouring cancellation
+fn copy_stream(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    progress: &TransferProgress,
+) -> Result<u64> {
     let mut buffer = vec![0u8; BUFFER_SIZE];
     let mut total_bytes = 0u64;
 
     loop {
-        let bytes_read = local_file.read(&mut buffer)?;
+        if progress.is_cancelled() {
+            anyhow::bail!("Transfer cancelled");
+        }
+        let bytes_read = reader.read(&mut buffer)?;
         if bytes_read == 0 {
             break;
         }
-        remote_file.write_all(&buffer[..bytes_read])?;
+        writer.write_all(&buffer[..bytes_read])?;
         total_bytes += bytes_read as u64;
+        progress.add_bytes(bytes_read as u64);
     }
 
+    writer.flush()?;
     Ok(total_bytes)
 }
 
-/// Copy a remote file to a local destination
-fn copy_remote_to_local(source: &Path, dest: &Path, source_panel: &FilePanel) -> Result<u64> {
-    let sftp = source_panel
-        .get_sftp()
-        .context("Source is not a remote filesystem")?;
+/// Copy a file locally, keeping its permissions
+fn copy_local_to_local(source: &Path, dest: &Path, progress: &TransferProgress) -> Result<u64> {
+    let mut source_file = fs::File::open(source)
+        .with_context(|| format!("Failed to open local file: {}", source.display()))?;
+    let mut dest_file = fs::File::create(dest)
+        .with_context(|| format!("Failed to create local file: {}", dest.display()))?;
+
+    let total_bytes = copy_stream(&mut source_file, &mut dest_file, progress).with_context(|| {
+        format!(
+            "Failed to copy {} to {}",
+            source.display(),
+            dest.display()
+        )
+    })?;
+
+    let permissions = source_file.metadata()?.permissions();
+    fs::set_permissions(dest, permissions)?;
+
+    Ok(total_bytes)
+}
+
+/// Copy a local file to a remote destination
+fn copy_local_to_remote(
+    source: &Path,
+    dest: &Path,
+    dest_sftp: &Mutex<Sftp>,
+    progress: &TransferProgress,
+) -> Result<u64> {
+    // Read local file
+    let mut local_file = fs::File::open(source)
+        .with_context(|| format!("Failed to open local file: {}", source.display()))?;
 
-    let sftp_guard = sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+    // Create remote file; the SFTP lock is only held while opening so the
+    // UI can keep listing directories during the transfer
+    let mut remote_file = lock_sftp(dest_sftp)?
+        .create(dest)
+        .with_context(|| format!("Failed to create remote file: {}", dest.display()))?;
+
+    copy_stream(&mut local_file, &mut remote_file, progress)
+}
 
+/// Copy a remote file to a local destination
+fn copy_remote_to_local(
+    source: &Path,
+    dest: &Path,
+    source_sftp: &Mutex<Sftp>,
+    progress: &TransferProgress,
+) -> Result<u64> {
     // Open remote file
-    let mut remote_file = sftp_guard
+    let mut remote_file = lock_sftp(source_sftp)?
         .open(source)
         .with_context(|| format!("Failed to open remote file: {}", source.display()))?;
 
@@ -91,106 +495,319 @@ fn copy_remote_to_local(source: &Path, dest: &Path, source_panel: &FilePanel) ->
     let mut local_file = fs::File::create(dest)
         .with_context(|| format!("Failed to create local file: {}", dest.display()))?;
 
-    // Transfer data
-    let mut buffer = vec![0u8; BUFFER_SIZE];
-    let mut total_bytes = 0u64;
-
-    loop {
-        let bytes_read = remote_file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        local_file.write_all(&buffer[..bytes_read])?;
-        total_bytes += bytes_read as u64;
-    }
-
-    Ok(total_bytes)
+    copy_stream(&mut remote_file, &mut local_file, progress)
 }
 
 /// Copy a file between two remote locations (download then upload)
 fn copy_remote_to_remote(
     source: &Path,
     dest: &Path,
-    source_panel: &FilePanel,
-    dest_panel: &FilePanel,
+    source_sftp: &Mutex<Sftp>,
+    dest_sftp: &Mutex<Sftp>,
+    progress: &TransferProgress,
 ) -> Result<u64> {
-    let source_sftp = source_panel
-        .get_sftp()
-        .context("Source is not a remote filesystem")?;
-    let dest_sftp = dest_panel
-        .get_sftp()
-        .context("Destination is not a remote filesystem")?;
-
-    let source_guard = source_sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-    let dest_guard = dest_sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-
     // Open source file
-    let mut source_file = source_guard
+    let mut source_file = lock_sftp(source_sftp)?
         .open(source)
         .with_context(|| format!("Failed to open remote source: {}", source.display()))?;
 
     // Create destination file
-    let mut dest_file = dest_guard
+    let mut dest_file = lock_sftp(dest_sftp)?
         .create(dest)
         .with_context(|| format!("Failed to create remote destination: {}", dest.display()))?;
 
-    // Transfer data
+    copy_stream(&mut source_file, &mut dest_file, progress)
+}
+
+/// Size of a regular file, or `None` if the path is missing or not a file
+pub fn file_size(endpoint: &Endpoint, path: &Path) -> Option<u64> {
+    match endpoint {
+        Endpoint::Remote(sftp) => {
+            let stat = lock_sftp(sftp).ok()?.stat(path).ok()?;
+            if stat.is_file() {
+                stat.size
+            } else {
+                None
+            }
+        }
+        Endpoint::Local => fs::metadata(path)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len()),
+    }
+}
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+trait ReadWriteSeek: Read + Write + Seek {}
+impl<T: Read + Write + Seek> ReadWriteSeek for T {}
+
+pub fn open_for_reading(endpoint: &Endpoint, path: &Path) -> Result<Box<dyn ReadSeek>> {
+    Ok(match endpoint {
+        Endpoint::Remote(sftp) => Box::new(
+            lock_sftp(sftp)?
+                .open(path)
+                .with_context(|| format!("Failed to open remote file: {}", path.display()))?,
+        ),
+        Endpoint::Local => Box::new(
+            fs::File::open(path)
+                .with_context(|| format!("Failed to open local file: {}", path.display()))?,
+        ),
+    })
+}
+
+/// Open an existing file for reading and writing without truncating it
+fn open_for_resuming(endpoint: &Endpoint, path: &Path) -> Result<Box<dyn ReadWriteSeek>> {
+    Ok(match endpoint {
+        Endpoint::Remote(sftp) => Box::new(
+            lock_sftp(sftp)?
+                .open_mode(path, OpenFlags::READ | OpenFlags::WRITE, 0o644, OpenType::File)
+                .with_context(|| format!("Failed to open remote file: {}", path.display()))?,
+        ),
+        Endpoint::Local => Box::new(
+            fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .with_context(|| format!("Failed to open local file: {}", path.display()))?,
+        ),
+    })
+}
+
+/// Checksum `len` bytes starting at `offset`
+fn checksum_range(file: &mut (impl Read + Seek + ?Sized), offset: u64, len: u64) -> Result<u64> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut hasher = DefaultHasher::new();
     let mut buffer = vec![0u8; BUFFER_SIZE];
-    let mut total_bytes = 0u64;
+    let mut remaining = len;
 
-    loop {
-        let bytes_read = source_file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+    while remaining > 0 {
+        let chunk = remaining.min(BUFFER_SIZE as u64) as usize;
+        file.read_exact(&mut buffer[..chunk])?;
+        hasher.write(&buffer[..chunk]);
+        remaining -= chunk as u64;
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Continue copying a single file onto a partial destination.
+///
+/// Both sides are positioned at the destination's current length and only the
+/// missing bytes are transferred. With `CopyMode::ResumeVerified` the last
+/// megabyte already written is checksummed against the source first, and a
+/// mismatch aborts without touching the destination.
+pub fn resume_file(
+    source: &Endpoint,
+    dest: &Endpoint,
+    source_path: &Path,
+    dest_path: &Path,
+    mode: CopyMode,
+    progress: &TransferProgress,
+) -> Result<TransferStats> {
+    let source_size = file_size(source, source_path)
+        .with_context(|| format!("Not a regular file: {}", source_path.display()))?;
+    let existing = file_size(dest, dest_path)
+        .with_context(|| format!("No partial file to resume: {}", dest_path.display()))?;
+    if existing > source_size {
+        anyhow::bail!(
+            "Destination is larger than the source: {}",
+            dest_path.display()
+        );
+    }
+
+    progress.start(1, source_size);
+    progress.set_current_file(source_path);
+    progress.add_bytes(existing);
+
+    let mut reader = open_for_reading(source, source_path)?;
+    let mut writer = open_for_resuming(dest, dest_path)?;
+
+    if mode == CopyMode::ResumeVerified && existing > 0 {
+        let tail = existing.min(RESUME_VERIFY_SIZE);
+        let offset = existing - tail;
+        let source_sum = checksum_range(&mut reader, offset, tail)?;
+        let dest_sum = checksum_range(&mut writer, offset, tail)?;
+        if source_sum != dest_sum {
+            anyhow::bail!(
+                "Partial file does not match the source, overwrite it instead: {}",
+                dest_path.display()
+            );
         }
-        dest_file.write_all(&buffer[..bytes_read])?;
-        total_bytes += bytes_read as u64;
     }
 
-    Ok(total_bytes)
+    reader.seek(SeekFrom::Start(existing))?;
+    writer.seek(SeekFrom::Start(existing))?;
+
+    let bytes = copy_stream(&mut reader, &mut writer, progress).with_context(|| {
+        format!(
+            "Failed to resume {} to {}",
+            source_path.display(),
+            dest_path.display()
+        )
+    })?;
+    progress.finish_file();
+
+    Ok(TransferStats { files: 1, bytes })
 }
 
-/// Delete a file from the source panel's filesystem
-pub fn delete_file(panel: &FilePanel, path: &Path) -> Result<()> {
-    if panel.is_remote() {
-        let sftp = panel.get_sftp().context("Not a remote filesystem")?;
-        let sftp_guard = sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-        sftp_guard
-            .unlink(path)
-            .with_context(|| format!("Failed to delete remote file: {}", path.display()))?;
-    } else {
-        fs::remove_file(path)
-            .with_context(|| format!("Failed to delete local file: {}", path.display()))?;
+/// Move a path with a single rename when both endpoints share a filesystem.
+///
+/// Returns `Ok(false)` when the endpoints are different filesystems (including
+/// local paths on different devices) or the destination is an existing
+/// directory, in which case the caller has to fall back to copying and
+/// deleting the source. An existing destination file is replaced.
+pub fn rename_path(
+    source: &Endpoint,
+    dest: &Endpoint,
+    source_path: &Path,
+    dest_path: &Path,
+) -> Result<bool> {
+    if source.same_filesystem(dest) {
+        check_not_into_itself("move", source_path, dest_path)?;
+    }
+    match (source, dest) {
+        (Endpoint::Local, Endpoint::Local) => {
+            if dest_path.is_dir() {
+                return Ok(false);
+            }
+            match fs::rename(source_path, dest_path) {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => Ok(false),
+                Err(e) => Err(e).with_context(|| {
+                    format!(
+                        "Failed to rename {} to {}",
+                        source_path.display(),
+                        dest_path.display()
+                    )
+                }),
+            }
+        }
+        (Endpoint::Remote(source_sftp), Endpoint::Remote(dest_sftp))
+            if Arc::ptr_eq(source_sftp, dest_sftp) =>
+        {
+            let sftp = lock_sftp(source_sftp)?;
+            let context = || {
+                format!(
+                    "Failed to rename remote {} to {}",
+                    source_path.display(),
+                    dest_path.display()
+                )
+            };
+            match sftp.stat(dest_path) {
+                Ok(stat) if stat.is_dir() => return Ok(false),
+                Ok(_) => {
+                    // SFTPv3 servers such as OpenSSH refuse to rename over an
+                    // existing file, so move it aside and drop it once the
+                    // new one is in place
+                    let aside = sibling_path(dest_path, "old");
+                    sftp.rename(dest_path, &aside, None).with_context(context)?;
+                    if let Err(e) = sftp.rename(source_path, dest_path, None) {
+                        let _ = sftp.rename(&aside, dest_path, None);
+                        return Err(e).with_context(context);
+                    }
+                    let _ = sftp.unlink(&aside);
+                }
+                Err(_) => sftp.rename(source_path, dest_path, None).with_context(context)?,
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
     }
-    Ok(())
 }
 
-/// Delete a directory from the source panel's filesystem
-pub fn delete_directory(panel: &FilePanel, path: &Path) -> Result<()> {
-    if panel.is_remote() {
-        let sftp = panel.get_sftp().context("Not a remote filesystem")?;
-        let sftp_guard = sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-        sftp_guard
-            .rmdir(path)
-            .with_context(|| format!("Failed to delete remote directory: {}", path.display()))?;
-    } else {
-        fs::remove_dir_all(path)
-            .with_context(|| format!("Failed to delete local directory: {}", path.display()))?;
+/// A hidden name in the same directory as `path`, e.g. `.app.conf.old-1234`
+pub fn sibling_path(path: &Path, tag: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}-{}", name, tag, std::process::id()))
+}
+
+/// Delete a file from the endpoint's filesystem
+pub fn delete_file(endpoint: &Endpoint, path: &Path) -> Result<()> {
+    match endpoint {
+        Endpoint::Remote(sftp) => {
+            lock_sftp(sftp)?
+                .unlink(path)
+                .with_context(|| format!("Failed to delete remote file: {}", path.display()))?;
+        }
+        Endpoint::Local => {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to delete local file: {}", path.display()))?;
+        }
     }
     Ok(())
 }
 
-/// Create a directory in the panel's filesystem
-pub fn create_directory(panel: &FilePanel, path: &Path) -> Result<()> {
-    if panel.is_remote() {
-        let sftp = panel.get_sftp().context("Not a remote filesystem")?;
-        let sftp_guard = sftp.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-        sftp_guard
-            .mkdir(path, 0o755)
-            .with_context(|| format!("Failed to create remote directory: {}", path.display()))?;
-    } else {
-        fs::create_dir(path)
-            .with_context(|| format!("Failed to create local directory: {}", path.display()))?;
+/// Delete a directory and everything below it from the endpoint's filesystem.
+///
+/// Entries are removed bottom-up and symlinks are unlinked rather than
+/// followed. If an entry cannot be removed the walk stops and the error
+/// names that entry.
+pub fn delete_directory(endpoint: &Endpoint, path: &Path) -> Result<()> {
+    match endpoint {
+        Endpoint::Remote(sftp) => delete_remote_tree(sftp, path),
+        Endpoint::Local => delete_local_tree(path),
+    }
+}
+
+fn delete_local_tree(path: &Path) -> Result<()> {
+    let read_dir = fs::read_dir(path)
+        .with_context(|| format!("Failed to read local directory: {}", path.display()))?;
+
+    for entry in read_dir {
+        let entry = entry?;
+        let child = entry.path();
+        if entry.file_type()?.is_dir() {
+            delete_local_tree(&child)?;
+        } else {
+            fs::remove_file(&child)
+                .with_context(|| format!("Failed to delete local file: {}", child.display()))?;
+        }
+    }
+
+    fs::remove_dir(path)
+        .with_context(|| format!("Failed to delete local directory: {}", path.display()))
+}
+
+/// Lock the session for each request, so other users of it are not held up
+/// for the whole tree
+fn delete_remote_tree(sftp: &Arc<Mutex<Sftp>>, path: &Path) -> Result<()> {
+    // readdir reports lstat attributes, so symlinked directories show up as links
+    let dir_entries = lock_sftp(sftp)?
+        .readdir(path)
+        .with_context(|| format!("Failed to read remote directory: {}", path.display()))?;
+
+    for (child, stat) in dir_entries {
+        if stat.file_type().is_dir() {
+            delete_remote_tree(sftp, &child)?;
+        } else {
+            lock_sftp(sftp)?
+                .unlink(&child)
+                .with_context(|| format!("Failed to delete remote file: {}", child.display()))?;
+        }
+    }
+
+    lock_sftp(sftp)?
+        .rmdir(path)
+        .with_context(|| format!("Failed to delete remote directory: {}", path.display()))
+}
+
+/// Create a directory in the endpoint's filesystem
+pub fn create_directory(endpoint: &Endpoint, path: &Path) -> Result<()> {
+    match endpoint {
+        Endpoint::Remote(sftp) => {
+            lock_sftp(sftp)?
+                .mkdir(path, 0o755)
+                .with_context(|| format!("Failed to create remote directory: {}", path.display()))?;
+        }
+        Endpoint::Local => {
+            fs::create_dir(path)
+                .with_context(|| format!("Failed to create local directory: {}", path.display()))?;
+        }
     }
     Ok(())
 }
@@ -205,6 +822,14 @@ mod tests {
         FilePanel::new(LocalFileSystem::new(), dir.to_path_buf()).unwrap()
     }
 
+    fn item(source_path: &Path, dest_path: &Path) -> TransferItem {
+        TransferItem {
+            source_path: source_path.to_path_buf(),
+            dest_path: dest_path.to_path_buf(),
+            is_dir: source_path.is_dir(),
+        }
+    }
+
     #[test]
     fn test_copy_local_to_local() {
         let temp_dir = TempDir::new().unwrap();
@@ -213,7 +838,7 @@ mod tests {
 
         fs::write(&source, "Hello, World!").unwrap();
 
-        let bytes = copy_local_to_local(&source, &dest).unwrap();
+        let bytes = copy_local_to_local(&source, &dest, &TransferProgress::new()).unwrap();
 
         assert_eq!(bytes, 13);
         assert_eq!(fs::read_to_string(&dest).unwrap(), "Hello, World!");
@@ -231,13 +856,266 @@ mod tests {
         let dest_panel = create_test_panel(dest_dir.path());
 
         let dest_file = dest_dir.path().join("test.txt");
-        let bytes = copy_file(&source_panel, &dest_panel, &source_file, &dest_file).unwrap();
+        let stats = copy_files(
+            &Endpoint::of(&source_panel),
+            &Endpoint::of(&dest_panel),
+            &[item(&source_file, &dest_file)],
+            &TransferProgress::new(),
+        )
+        .unwrap();
 
-        assert_eq!(bytes, 12);
+        assert_eq!(stats.bytes, 12);
+        assert_eq!(stats.files, 1);
         assert!(dest_file.exists());
         assert_eq!(fs::read_to_string(&dest_file).unwrap(), "Test content");
     }
 
+    #[test]
+    fn test_copy_directory_local_panels() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+
+        let tree = source_dir.path().join("project");
+        fs::create_dir_all(tree.join("src/nested")).unwrap();
+        fs::create_dir(tree.join("empty")).unwrap();
+        fs::write(tree.join("README"), "readme").unwrap();
+        fs::write(tree.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(tree.join("src/nested/mod.rs"), "").unwrap();
+
+        let source_panel = create_test_panel(source_dir.path());
+        let dest_panel = create_test_panel(dest_dir.path());
+
+        let dest = dest_dir.path().join("project");
+        let stats = copy_files(
+            &Endpoint::of(&source_panel),
+            &Endpoint::of(&dest_panel),
+            &[item(&tree, &dest)],
+            &TransferProgress::new(),
+        )
+        .unwrap();
+
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.bytes, 18);
+        assert!(dest.join("empty").is_dir());
+        assert_eq!(fs::read_to_string(dest.join("src/main.rs")).unwrap(), "fn main() {}");
+        assert!(dest.join("src/nested/mod.rs").is_file());
+    }
+
+    #[test]
+    fn test_copy_directory_into_itself_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+
+        let panel = create_test_panel(temp_dir.path());
+        let result = copy_files(
+            &Endpoint::of(&panel),
+            &Endpoint::of(&panel),
+            &[item(&tree, &tree.join("tree"))],
+            &TransferProgress::new(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_copy_reports_progress() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+
+        let tree = source_dir.path().join("data");
+        fs::create_dir(&tree).unwrap();
+        fs::write(tree.join("a.bin"), vec![0u8; BUFFER_SIZE * 2 + 10]).unwrap();
+        fs::write(tree.join("b.bin"), "tail").unwrap();
+
+        let progress = TransferProgress::new();
+        copy_files(
+            &Endpoint::Local,
+            &Endpoint::Local,
+            &[item(&tree, &dest_dir.path().join("data"))],
+            &progress,
+        )
+        .unwrap();
+
+        assert_eq!(progress.files_total(), 2);
+        assert_eq!(progress.files_done(), 2);
+        assert_eq!(progress.bytes_total(), (BUFFER_SIZE * 2 + 14) as u64);
+        assert_eq!(progress.bytes_done(), progress.bytes_total());
+        assert_eq!(progress.ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_copy_multiple_items() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+
+        fs::write(source_dir.path().join("a.txt"), "aaa").unwrap();
+        fs::create_dir(source_dir.path().join("dir")).unwrap();
+        fs::write(source_dir.path().join("dir/b.txt"), "bb").unwrap();
+
+        let items: Vec<TransferItem> = ["a.txt", "dir"]
+            .iter()
+            .map(|name| item(&source_dir.path().join(name), &dest_dir.path().join(name)))
+            .collect();
+
+        let progress = TransferProgress::new();
+        let stats = copy_files(&Endpoint::Local, &Endpoint::Local, &items, &progress).unwrap();
+
+        assert_eq!(stats, TransferStats { files: 2, bytes: 5 });
+        assert_eq!(progress.bytes_total(), 5);
+        assert_eq!(fs::read_to_string(dest_dir.path().join("dir/b.txt")).unwrap(), "bb");
+    }
+
+    #[test]
+    fn test_cancelled_copy_removes_partial_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("big.bin");
+        let dest = temp_dir.path().join("copy.bin");
+        fs::write(&source, vec![0u8; BUFFER_SIZE]).unwrap();
+
+        let progress = TransferProgress::new();
+        progress.cancel();
+        let result = copy_files(
+            &Endpoint::Local,
+            &Endpoint::Local,
+            &[item(&source, &dest)],
+            &progress,
+        );
+
+        assert!(result.is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_resume_local_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("image.iso");
+        let dest = temp_dir.path().join("partial.iso");
+
+        let data: Vec<u8> = (0..BUFFER_SIZE * 3).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &data).unwrap();
+        fs::write(&dest, &data[..BUFFER_SIZE + 100]).unwrap();
+
+        let progress = TransferProgress::new();
+        let stats = resume_file(
+            &Endpoint::Local,
+            &Endpoint::Local,
+            &source,
+            &dest,
+            CopyMode::ResumeVerified,
+            &progress,
+        )
+        .unwrap();
+
+        assert_eq!(stats.bytes, (BUFFER_SIZE * 2 - 100) as u64);
+        assert_eq!(progress.bytes_done(), data.len() as u64);
+        assert_eq!(fs::read(&dest).unwrap(), data);
+    }
+
+    #[test]
+    fn test_resume_rejects_mismatched_partial_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.bin");
+        let dest = temp_dir.path().join("dest.bin");
+        fs::write(&source, "the real contents").unwrap();
+        fs::write(&dest, "something else").unwrap();
+
+        let result = resume_file(
+            &Endpoint::Local,
+            &Endpoint::Local,
+            &source,
+            &dest,
+            CopyMode::ResumeVerified,
+            &TransferProgress::new(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "something else");
+    }
+
+    #[test]
+    fn test_file_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("sized.txt");
+        fs::write(&file, "12345").unwrap();
+
+        assert_eq!(file_size(&Endpoint::Local, &file), Some(5));
+        assert_eq!(file_size(&Endpoint::Local, temp_dir.path()), None);
+        assert_eq!(file_size(&Endpoint::Local, &temp_dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn test_rename_local_directory() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+
+        let tree = source_dir.path().join("logs");
+        fs::create_dir_all(tree.join("2024")).unwrap();
+        fs::write(tree.join("2024/app.log"), "log line").unwrap();
+
+        let source_panel = create_test_panel(source_dir.path());
+        let dest_panel = create_test_panel(dest_dir.path());
+
+        let dest = dest_dir.path().join("logs");
+        let renamed = rename_path(
+            &Endpoint::of(&source_panel),
+            &Endpoint::of(&dest_panel),
+            &tree,
+            &dest,
+        )
+        .unwrap();
+
+        assert!(renamed);
+        assert!(!tree.exists());
+        assert_eq!(fs::read_to_string(dest.join("2024/app.log")).unwrap(), "log line");
+    }
+
+    #[test]
+    fn test_rename_over_existing() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("notes.txt");
+        let dest_dir = temp_dir.path().join("archive");
+        fs::write(&source, "new").unwrap();
+        fs::create_dir_all(dest_dir.join("notes.txt")).unwrap();
+        fs::write(dest_dir.join("old.txt"), "old").unwrap();
+
+        let panel = create_test_panel(temp_dir.path());
+        let endpoint = Endpoint::of(&panel);
+
+        // A file replaces a file, but a directory is left to copying
+        assert!(rename_path(&endpoint, &endpoint, &source, &dest_dir.join("old.txt")).unwrap());
+        assert_eq!(fs::read_to_string(dest_dir.join("old.txt")).unwrap(), "new");
+        fs::write(&source, "new").unwrap();
+        assert!(!rename_path(&endpoint, &endpoint, &source, &dest_dir.join("notes.txt")).unwrap());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_sibling_path() {
+        assert_eq!(
+            sibling_path(Path::new("/etc/app.conf"), "upload"),
+            PathBuf::from(format!("/etc/.app.conf.upload-{}", std::process::id()))
+        );
+    }
+
+    #[test]
+    fn test_rename_directory_into_itself_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+
+        let panel = create_test_panel(temp_dir.path());
+        let result = rename_path(
+            &Endpoint::of(&panel),
+            &Endpoint::of(&panel),
+            &tree,
+            &tree.join("tree"),
+        );
+
+        assert!(result.is_err());
+        assert!(tree.is_dir());
+    }
+
     #[test]
     fn test_delete_local_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -245,7 +1123,7 @@ mod tests {
         fs::write(&file_path, "Delete me").unwrap();
 
         let panel = create_test_panel(temp_dir.path());
-        delete_file(&panel, &file_path).unwrap();
+        delete_file(&Endpoint::of(&panel), &file_path).unwrap();
 
         assert!(!file_path.exists());
     }
@@ -256,7 +1134,7 @@ mod tests {
         let new_dir = temp_dir.path().join("new_folder");
 
         let panel = create_test_panel(temp_dir.path());
-        create_directory(&panel, &new_dir).unwrap();
+        create_directory(&Endpoint::of(&panel), &new_dir).unwrap();
 
         assert!(new_dir.is_dir());
     }
@@ -269,8 +1147,27 @@ mod tests {
         fs::write(dir_to_delete.join("file.txt"), "content").unwrap();
 
         let panel = create_test_panel(temp_dir.path());
-        delete_directory(&panel, &dir_to_delete).unwrap();
+        delete_directory(&Endpoint::of(&panel), &dir_to_delete).unwrap();
+
+        assert!(!dir_to_delete.exists());
+    }
+
+    #[test]
+    fn test_delete_local_directory_keeps_symlink_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("keep.txt"), "keep").unwrap();
+
+        let dir_to_delete = temp_dir.path().join("nested");
+        fs::create_dir_all(dir_to_delete.join("a/b")).unwrap();
+        fs::write(dir_to_delete.join("a/b/file.txt"), "content").unwrap();
+        std::os::unix::fs::symlink(&target, dir_to_delete.join("link")).unwrap();
+
+        let panel = create_test_panel(temp_dir.path());
+        delete_directory(&Endpoint::of(&panel), &dir_to_delete).unwrap();
 
         assert!(!dir_to_delete.exists());
+        assert!(target.join("keep.txt").exists());
     }
 }
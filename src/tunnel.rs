@@ -0,0 +1,100 @@
+//! Tunnels through jump hosts
+//!
+//! libssh2 can only run a session over a real socket, so a `direct-tcpip`
+//! channel on the jump host is bridged to one end of a local socket pair by a
+//! background thread, and the next session is started on the other end.
+
+use anyhow::{Context, Result};
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// How long the pump sleeps when neither side had anything to move
+const IDLE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Bridge `channel` to a local socket and return the socket to connect over.
+/// The session is kept alive by the bridging thread until either side closes.
+pub fn forward(session: Session, channel: Channel) -> Result<UnixStream> {
+    let (local, bridged) = UnixStream::pair().context("Failed to create tunnel socket")?;
+    bridged
+        .set_nonblocking(true)
+        .context("Failed to configure tunnel socket")?;
+
+    // Only the pump uses the jump host session from now on
+    session.set_blocking(false);
+    thread::spawn(move || pump(session, channel, bridged));
+
+    Ok(local)
+}
+
+/// Move bytes both ways until one side closes or fails
+fn pump(_session: Session, mut channel: Channel, mut socket: UnixStream) {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut to_socket: Vec<u8> = Vec::new();
+    let mut to_channel: Vec<u8> = Vec::new();
+
+    loop {
+        let mut progressed = false;
+
+        // Jump host -> local socket
+        if to_socket.is_empty() {
+            match channel.read(&mut buf) {
+                Ok(0) if channel.eof() => break,
+                Ok(0) => {}
+                Ok(n) => {
+                    to_socket.extend_from_slice(&buf[..n]);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+        if !to_socket.is_empty() {
+            match socket.write(&to_socket) {
+                Ok(n) => {
+                    to_socket.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        // Local socket -> jump host
+        if to_channel.is_empty() {
+            match socket.read(&mut buf) {
+                Ok(0) => {
+                    // The session on top has gone away
+                    let _ = channel.send_eof();
+                    break;
+                }
+                Ok(n) => {
+                    to_channel.extend_from_slice(&buf[..n]);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+        if !to_channel.is_empty() {
+            match channel.write(&to_channel) {
+                Ok(n) => {
+                    to_channel.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !progressed {
+            thread::sleep(IDLE_INTERVAL);
+        }
+    }
+
+    let _ = channel.close();
+}
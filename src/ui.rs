@@ -2,9 +2,11 @@ mod panels;
 mod popups;
 mod statusbar;
 mod terminal;
+mod viewer;
 
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::{Block, Borders},
     Frame,
 };
 
@@ -12,25 +14,114 @@ use crate::app::{ActivePanel, App};
 
 // Re-export submodule functions for external use if needed
 pub use panels::draw_panel;
-pub use popups::{draw_confirmation_popup, draw_help_popup};
+pub use popups::{
+    draw_confirmation_popup, draw_help_popup, draw_hotlist_popup, draw_input_popup,
+    draw_recent_popup, draw_transfer_popup,
+};
 pub use statusbar::{draw_function_bar, draw_status_bar};
 pub use terminal::draw_terminal;
+pub use viewer::draw_viewer;
 
 /// Main draw function for the application
 pub fn draw(frame: &mut Frame, app: &mut App) {
     let size = frame.area();
 
+    // Shells are sized for the pane even while it is hidden
+    let terminal_area = terminal_inner_area(size, app);
+    app.resize_shells(terminal_area.height, terminal_area.width);
+
+    if app.terminal_fullscreen {
+        draw_fullscreen_terminal(frame, app);
+    } else {
+        draw_panels(frame, app);
+    }
+
+    // The viewer covers the whole screen
+    if let Some(viewer) = app.viewer.as_mut() {
+        draw_viewer(frame, size, viewer);
+    }
+
+    // Draw help popup if active
+    if app.show_help {
+        draw_help_popup(frame, size);
+    }
+
+    // Draw transfer progress if a job is running in the foreground
+    if app.transfer_dialog_visible() {
+        draw_transfer_popup(frame, size, app);
+    }
+
+    if app.recent_popup.is_some() {
+        draw_recent_popup(frame, size, app);
+    }
+
+    if app.hotlist_popup.is_some() {
+        draw_hotlist_popup(frame, size, app);
+    }
+
+    // Draw input dialog if active
+    if app.input_dialog.is_some() {
+        draw_input_popup(frame, size, app);
+    }
+
+    // Draw confirmation dialog if active
+    if app.confirmation_dialog.is_some() {
+        draw_confirmation_popup(frame, size, app);
+    }
+}
+
+/// Where the shell's screen goes inside the terminal pane on a screen of
+/// `size`, whether or not the pane is shown
+pub fn terminal_inner_area(size: Rect, app: &App) -> Rect {
+    let pane = if app.terminal_fullscreen {
+        fullscreen_layout(size)[0]
+    } else {
+        split_layout(size)[1]
+    };
+    Block::default().borders(Borders::ALL).inner(pane)
+}
+
+/// Terminal, status bar and function key bar
+fn fullscreen_layout(size: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(3),    // Terminal area
+            Constraint::Length(1), // Status bar
+            Constraint::Length(1), // Function key bar
+        ])
+        .split(size)
+}
+
+/// Panels and terminal sharing the screen, then the two bars
+fn split_layout(size: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(50), // Panels area
+            Constraint::Percentage(50), // Terminal area
+            Constraint::Length(1),      // Status bar
+            Constraint::Length(1),      // Function key bar
+        ])
+        .split(size)
+}
+
+/// The terminal over everything but the status and function key bars
+fn draw_fullscreen_terminal(frame: &mut Frame, app: &mut App) {
+    let chunks = fullscreen_layout(frame.area());
+
+    draw_terminal(frame, chunks[0], app);
+    draw_status_bar(frame, chunks[1], app);
+    draw_function_bar(frame, chunks[2]);
+}
+
+/// Both panels, with the terminal under them when it is shown
+fn draw_panels(frame: &mut Frame, app: &mut App) {
+    let size = frame.area();
+
     // Main layout: panels + optional terminal + status bar + function key bar
     let main_chunks = if app.show_terminal {
-        Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Percentage(50), // Panels area
-                Constraint::Percentage(50), // Terminal area
-                Constraint::Length(1),      // Status bar
-                Constraint::Length(1),      // Function key bar
-            ])
-            .split(size)
+        split_layout(size)
     } else {
         Layout::default()
             .direction(Direction::Vertical)
@@ -44,7 +135,7 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 
     // Draw terminal FIRST if visible to ensure proper clearing
     if app.show_terminal {
-        terminal::draw_terminal(frame, main_chunks[1], app);
+        draw_terminal(frame, main_chunks[1], app);
     }
 
     // Split panels horizontally
@@ -58,7 +149,7 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
     app.set_visible_rows(visible_rows);
 
     // Draw left panel
-    panels::draw_panel(
+    draw_panel(
         frame,
         panel_chunks[0],
         &app.left_panel,
@@ -68,11 +159,11 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 
     // Draw right panel
     let right_title = if let Some(ref remote) = app.remote_connection {
-        format!("Remote: {}", remote)
+        format!("Remote: {}", remote.host)
     } else {
         "Local".to_string()
     };
-    panels::draw_panel(
+    draw_panel(
         frame,
         panel_chunks[1],
         &app.right_panel,
@@ -82,19 +173,9 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 
     // Draw status bar
     let status_bar_idx = if app.show_terminal { 2 } else { 1 };
-    statusbar::draw_status_bar(frame, main_chunks[status_bar_idx], app);
+    draw_status_bar(frame, main_chunks[status_bar_idx], app);
 
     // Draw function key bar
     let function_bar_idx = if app.show_terminal { 3 } else { 2 };
-    statusbar::draw_function_bar(frame, main_chunks[function_bar_idx]);
-
-    // Draw help popup if active
-    if app.show_help {
-        popups::draw_help_popup(frame, size);
-    }
-
-    // Draw confirmation dialog if active
-    if app.confirmation_dialog.is_some() {
-        popups::draw_confirmation_popup(frame, size, app);
-    }
+    draw_function_bar(frame, main_chunks[function_bar_idx]);
 }
@@ -2,7 +2,7 @@ use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
     Frame,
 };
 
@@ -21,12 +21,23 @@ pub fn draw_panel(
         Style::default().fg(Color::Gray)
     };
 
-    let title_with_path = format!(" {} - {} ", title, panel.current_path.display());
+    let title_with_path = format!(
+        " {} - {} [{}{}] ",
+        title,
+        panel.current_path.display(),
+        panel.sort_order().label(),
+        if panel.show_hidden() { "" } else { ", dotfiles hidden" }
+    );
 
-    let block = Block::default()
+    let mut block = Block::default()
         .borders(Borders::ALL)
         .title(title_with_path)
         .border_style(border_style);
+    if let Some(filter) = panel.filter() {
+        block = block.title_bottom(
+            Line::from(format!(" Filter: {} ", filter.text())).style(Style::default().fg(Color::Yellow)),
+        );
+    }
 
     let inner_area = block.inner(area);
     frame.render_widget(block, area);
@@ -36,6 +47,7 @@ pub fn draw_panel(
         .visible_entries()
         .map(|(idx, entry)| {
             let is_selected = idx == panel.selected_index;
+            let is_marked = panel.is_marked(entry);
             
             // Format the line: name | size | date
             let name = if entry.is_dir {
@@ -66,7 +78,11 @@ pub fn draw_panel(
             let style = if is_selected {
                 Style::default()
                     .bg(Color::Blue)
-                    .fg(Color::White)
+                    .fg(if is_marked { Color::LightRed } else { Color::White })
+                    .add_modifier(Modifier::BOLD)
+            } else if is_marked {
+                Style::default()
+                    .fg(Color::LightRed)
                     .add_modifier(Modifier::BOLD)
             } else if entry.is_dir {
                 Style::default().fg(Color::Yellow)
@@ -80,4 +96,33 @@ pub fn draw_panel(
 
     let list = List::new(items);
     frame.render_widget(list, inner_area);
+
+    if let Some(text) = panel.quick_search() {
+        draw_quick_search(frame, inner_area, text);
+    }
+}
+
+/// The quick search box over the bottom of the panel
+fn draw_quick_search(frame: &mut Frame, area: Rect, text: &str) {
+    let width = 30.min(area.width);
+    let height = 3.min(area.height);
+    let box_area = Rect {
+        x: area.x,
+        y: area.y + area.height - height,
+        width,
+        height,
+    };
+
+    let input = Paragraph::new(text.to_string()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Search ")
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    frame.render_widget(Clear, box_area);
+    frame.render_widget(input, box_area);
+    frame.set_cursor_position((
+        box_area.x + 1 + (text.chars().count() as u16).min(width.saturating_sub(3)),
+        box_area.y + 1,
+    ));
 }
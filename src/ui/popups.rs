@@ -1,16 +1,19 @@
 use ratatui::{
-    layout::Rect,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Paragraph},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
+use std::time::Duration;
+use unicode_width::UnicodeWidthStr;
 
 use crate::app::{App, ConfirmationAction};
+use crate::filesystem::{format_file_size, FileEntry};
 
 pub fn draw_help_popup(frame: &mut Frame, area: Rect) {
     let popup_width = 50;
-    let popup_height = 15;
+    let popup_height = 31;
     
     let popup_area = Rect {
         x: (area.width.saturating_sub(popup_width)) / 2,
@@ -29,11 +32,26 @@ pub fn draw_help_popup(frame: &mut Frame, area: Rect) {
         Line::from("  Enter     Enter directory"),
         Line::from("  Backspace Parent directory"),
         Line::from("  Tab       Switch panels"),
+        Line::from("  Alt-←/→   Back/forward  Alt-↓ Recent dirs"),
+        Line::from("  Ctrl-\\    Directory hotlist"),
+        Line::from("  Ctrl-G/:  Go to path (Tab completes)"),
         Line::from(""),
         Line::from("Commands:"),
         Line::from("  F1/h Help    F5/c Copy     F8/d Delete"),
         Line::from("  F3/v View    F6/m Move     F10/q Quit"),
-        Line::from("  F4/e Edit    F7/n MkDir"),
+        Line::from("  F4/e Edit    F7/n MkDir    j    Transfers"),
+        Line::from("  Shift-F6/r Rename   ↑/↓ in prompts: history"),
+        Line::from("  F9/t Terminal       Ctrl-O Full-screen shell"),
+        Line::from(""),
+        Line::from("Selection:"),
+        Line::from("  Ins/Space Mark file   +/- Mark/unmark by mask"),
+        Line::from("  *         Invert marks"),
+        Line::from("  Alt-letter Quick search  Ctrl-F Filter"),
+        Line::from("  .         Show or hide dotfiles"),
+        Line::from(""),
+        Line::from("Sorting (again to reverse):"),
+        Line::from("  Ctrl-F3 Name   Ctrl-F4 Ext    Ctrl-F5 Time"),
+        Line::from("  Ctrl-F6 Size   Ctrl-F7 None   Ctrl-F8 Dirs top"),
     ];
 
     let help_paragraph = Paragraph::new(help_text)
@@ -51,37 +69,76 @@ pub fn draw_help_popup(frame: &mut Frame, area: Rect) {
 }
 
 pub fn draw_confirmation_popup(frame: &mut Frame, area: Rect, app: &App) {
-    let (title, message) = match &app.confirmation_dialog {
-        Some(ConfirmationAction::Copy { source, dest_path }) => {
+    let yes_no_keys = Line::from(vec![
+        Span::styled("[Y]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+        Span::raw("es   "),
+        Span::styled("[N]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw("o   "),
+        Span::styled("[ESC]", Style::default().fg(Color::Gray)),
+        Span::raw(" Cancel"),
+    ]);
+
+    let (title, message, keys) = match &app.confirmation_dialog {
+        Some(ConfirmationAction::Copy { sources, dest_dir, existing }) => {
             let msg = format!(
-                "Copy '{}' to {}?",
-                source.name,
-                dest_path.display()
+                "Copy {} to {}?{}",
+                describe_entries(sources),
+                dest_dir.display(),
+                describe_overwrite(*existing)
             );
-            ("Confirm Copy", msg)
+            ("Confirm Copy", msg, yes_no_keys)
         }
-        Some(ConfirmationAction::Move { source, dest_path }) => {
+        Some(ConfirmationAction::Move { sources, dest_dir, existing }) => {
             let msg = format!(
-                "Move '{}' to {}?",
-                source.name,
-                dest_path.display()
+                "Move {} to {}?{}",
+                describe_entries(sources),
+                dest_dir.display(),
+                describe_overwrite(*existing)
             );
-            ("Confirm Move", msg)
+            ("Confirm Move", msg, yes_no_keys)
         }
-        Some(ConfirmationAction::Delete { entry }) => {
-            let item_type = if entry.is_dir { "directory" } else { "file" };
+        Some(ConfirmationAction::Delete { entries }) => {
+            let msg = format!("Delete {}?", describe_entries(entries));
+            ("Confirm Delete", msg, yes_no_keys)
+        }
+        Some(ConfirmationAction::UploadEdited { name }) => {
+            let msg = format!("'{}' changed on the server while editing. Upload anyway?", name);
+            ("Remote File Changed", msg, yes_no_keys)
+        }
+        Some(ConfirmationAction::SwitchHost { bookmark, current }) => {
             let msg = format!(
-                "Delete {} '{}'?",
-                item_type,
-                entry.name
+                "Disconnect from {} to open '{}' on {}? Programs running in its terminal will end.",
+                current,
+                bookmark.name,
+                bookmark.location.label()
             );
-            ("Confirm Delete", msg)
+            ("Switch Host", msg, yes_no_keys)
+        }
+        Some(ConfirmationAction::Resume { source, existing, .. }) => {
+            let msg = format!(
+                "'{}' is partly there ({} of {}).",
+                source.name,
+                format_file_size(*existing),
+                format_file_size(source.size)
+            );
+            let keys = Line::from(vec![
+                Span::styled("[R]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::raw("esume  "),
+                Span::styled("[V]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::raw("erify+resume  "),
+                Span::styled("[O]", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw("verwrite  "),
+                Span::styled("[ESC]", Style::default().fg(Color::Gray)),
+                Span::raw(" Cancel"),
+            ]);
+            ("Resume Transfer", msg, keys)
         }
         None => return,
     };
 
     let popup_width = 60;
-    let popup_height = 8;
+    let message_lines = wrapped_line_count(&message, popup_width as usize - 2) as u16;
+    let popup_height = message_lines + 7;
     
     let popup_area = Rect {
         x: (area.width.saturating_sub(popup_width)) / 2,
@@ -97,14 +154,7 @@ pub fn draw_confirmation_popup(frame: &mut Frame, area: Rect, app: &App) {
             Style::default().fg(Color::White),
         )),
         Line::from(""),
-        Line::from(vec![
-            Span::styled("[Y]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-            Span::raw("es   "),
-            Span::styled("[N]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-            Span::raw("o   "),
-            Span::styled("[ESC]", Style::default().fg(Color::Gray)),
-            Span::raw(" Cancel"),
-        ]),
+        keys,
     ];
 
     let confirmation_paragraph = Paragraph::new(confirmation_text)
@@ -114,13 +164,284 @@ pub fn draw_confirmation_popup(frame: &mut Frame, area: Rect, app: &App) {
                 .title(format!(" {} ", title))
                 .border_style(Style::default().fg(Color::Yellow)),
         )
-        .style(Style::default().bg(Color::Black));
+        .style(Style::default().bg(Color::Black))
+        .wrap(Wrap { trim: true });
 
     // Clear the area first
     frame.render_widget(Clear, popup_area);
     frame.render_widget(confirmation_paragraph, popup_area);
 }
 
+/// How many lines `text` takes when word-wrapped to `width` columns
+fn wrapped_line_count(text: &str, width: usize) -> usize {
+    let mut lines = 1;
+    let mut used = 0;
+    for word in text.split_whitespace() {
+        let len = word.width();
+        if used == 0 {
+            used = len;
+        } else if used + 1 + len <= width {
+            used += 1 + len;
+        } else {
+            lines += 1;
+            used = len;
+        }
+        // Words longer than a line are broken up
+        while used > width {
+            lines += 1;
+            used -= width;
+        }
+    }
+    lines
+}
+
+pub fn draw_transfer_popup(frame: &mut Frame, area: Rect, app: &App) {
+    let job = match app.transfers.active() {
+        Some(job) => job,
+        None => return,
+    };
+    let progress = &job.progress;
+
+    let popup_width = 64;
+    let popup_height = 10;
+
+    let popup_area = Rect {
+        x: (area.width.saturating_sub(popup_width)) / 2,
+        y: (area.height.saturating_sub(popup_height)) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" {} {} ", job.kind.verb(), job.name))
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let inner_area = block.inner(popup_area);
+
+    // Clear the area first
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // Current file
+            Constraint::Length(1), // Gauge
+            Constraint::Length(3), // Counters
+            Constraint::Min(1),    // Key hints
+        ])
+        .split(inner_area);
+
+    let current_file = progress.current_file();
+    let file_line = Paragraph::new(vec![
+        Line::from("File:"),
+        Line::from(Span::styled(
+            current_file.display().to_string(),
+            Style::default().fg(Color::White),
+        )),
+    ]);
+    frame.render_widget(file_line, chunks[0]);
+
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray))
+        .ratio(progress.ratio());
+    frame.render_widget(gauge, chunks[1]);
+
+    let throughput = progress
+        .throughput()
+        .map(|rate| format!("{}/s", format_file_size(rate as u64)))
+        .unwrap_or_else(|| "-".to_string());
+    let eta = progress
+        .eta()
+        .map(format_duration)
+        .unwrap_or_else(|| "--:--".to_string());
+
+    let counters = Paragraph::new(vec![
+        Line::from(format!(
+            "{} / {}   {}   ETA {}",
+            format_file_size(progress.bytes_done()),
+            format_file_size(progress.bytes_total()),
+            throughput,
+            eta
+        )),
+        Line::from(format!(
+            "Files {} / {}   Queued: {}",
+            progress.files_done(),
+            progress.files_total(),
+            app.transfers.queued_count()
+        )),
+    ]);
+    frame.render_widget(counters, chunks[2]);
+
+    let hints = Paragraph::new(Line::from(vec![
+        Span::styled("[ESC]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw(" Cancel   "),
+        Span::styled("[B]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+        Span::raw(" Background"),
+    ]));
+    frame.render_widget(hints, chunks[3]);
+}
+
+/// "file 'name'" for a single entry, "N items (SIZE)" for a marked set
+fn describe_entries(entries: &[FileEntry]) -> String {
+    match entries {
+        [entry] => {
+            let item_type = if entry.is_dir { "directory" } else { "file" };
+            format!("{} '{}'", item_type, entry.name)
+        }
+        _ => {
+            let size: u64 = entries.iter().filter(|e| !e.is_dir).map(|e| e.size).sum();
+            format!("{} items ({})", entries.len(), format_file_size(size))
+        }
+    }
+}
+
+/// Warning appended to a copy or move that replaces `existing` entries
+fn describe_overwrite(existing: usize) -> String {
+    match existing {
+        0 => String::new(),
+        1 => " It is already there and will be replaced.".to_string(),
+        n => format!(" {} of them are already there and will be replaced.", n),
+    }
+}
+
+pub fn draw_input_popup(frame: &mut Frame, area: Rect, app: &App) {
+    let dialog = match &app.input_dialog {
+        Some(dialog) => dialog,
+        None => return,
+    };
+
+    let popup_width = 60;
+    // An extra row for the validation error
+    let popup_height = if dialog.error.is_some() { 4 } else { 3 };
+
+    let popup_area = Rect {
+        x: (area.width.saturating_sub(popup_width)) / 2,
+        y: (area.height.saturating_sub(popup_height)) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" {} ", dialog.action.title()))
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+    let inner_area = block.inner(popup_area);
+
+    // Keep the cursor in view when the text is wider than the box
+    let cursor = dialog.input.cursor() as u16;
+    let scroll = cursor.saturating_sub(inner_area.width.saturating_sub(1));
+    let text = Paragraph::new(dialog.input.text())
+        .style(Style::default().fg(Color::White))
+        .scroll((0, scroll));
+
+    // Clear the area first
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+    frame.render_widget(text, inner_area);
+    if let Some(error) = &dialog.error {
+        let error_area = Rect {
+            y: inner_area.y + 1,
+            height: inner_area.height.saturating_sub(1),
+            ..inner_area
+        };
+        let error = Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red));
+        frame.render_widget(error, error_area);
+    }
+    frame.set_cursor_position((inner_area.x + cursor - scroll, inner_area.y));
+}
+
+/// The active panel's recently visited directories
+pub fn draw_recent_popup(frame: &mut Frame, area: Rect, app: &App) {
+    let selected = match app.recent_popup {
+        Some(selected) => selected,
+        None => return,
+    };
+    let items = app
+        .active_panel()
+        .recent_directories()
+        .iter()
+        .map(|path| ListItem::new(path.display().to_string()))
+        .collect();
+    let block = Block::default().title(" Recent Directories ");
+    draw_list_popup(frame, area, block, items, selected);
+}
+
+/// The directory hotlist with its key hints
+pub fn draw_hotlist_popup(frame: &mut Frame, area: Rect, app: &App) {
+    let selected = match app.hotlist_popup {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    let bookmarks = app.hotlist.bookmarks();
+    let name_width = bookmarks.iter().map(|b| b.name.chars().count()).max().unwrap_or(0);
+    let mut items: Vec<ListItem> = bookmarks
+        .iter()
+        .map(|bookmark| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:<width$}  ", bookmark.name, width = name_width)),
+                Span::styled(
+                    format!("{}:", bookmark.location.label()),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(bookmark.path.display().to_string()),
+            ]))
+        })
+        .collect();
+    if items.is_empty() {
+        items.push(ListItem::new(Span::styled(
+            "No bookmarks yet",
+            Style::default().fg(Color::Gray),
+        )));
+    }
+
+    let block = Block::default()
+        .title(" Directory Hotlist ")
+        .title_bottom(" Enter Go  Ins Add current  Del Remove  Esc Close ");
+    draw_list_popup(frame, area, block, items, selected);
+}
+
+/// A centered list with the selected row highlighted
+fn draw_list_popup(frame: &mut Frame, area: Rect, block: Block, items: Vec<ListItem>, selected: usize) {
+    let popup_width = 70;
+    let popup_height = items.len() as u16 + 2;
+
+    let popup_area = Rect {
+        x: (area.width.saturating_sub(popup_width)) / 2,
+        y: (area.height.saturating_sub(popup_height)) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let list = List::new(items)
+        .block(
+            block
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .style(Style::default().bg(Color::Black).fg(Color::White))
+        .highlight_style(Style::default().bg(Color::Blue).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default().with_selected(Some(selected));
+
+    // Clear the area first
+    frame.render_widget(Clear, popup_area);
+    frame.render_stateful_widget(list, popup_area, &mut state);
+}
+
+/// Format a duration as mm:ss, or hh:mm:ss once it exceeds an hour
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +459,19 @@ mod tests {
         
         // Just verify it doesn't panic
     }
+
+    #[test]
+    fn test_wrapped_line_count() {
+        assert_eq!(wrapped_line_count("", 10), 1);
+        assert_eq!(wrapped_line_count("Delete 'a'?", 20), 1);
+        assert_eq!(wrapped_line_count("Move 3 files to /srv?", 10), 3);
+        assert_eq!(wrapped_line_count("/a/very/long/path/name", 10), 3);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "00:00");
+        assert_eq!(format_duration(Duration::from_secs(75)), "01:15");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
 }
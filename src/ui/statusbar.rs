@@ -7,6 +7,7 @@ use ratatui::{
 };
 
 use crate::app::App;
+use crate::filesystem::format_file_size;
 
 pub fn draw_status_bar(frame: &mut Frame, area: Rect, app: &App) {
     let message = app
@@ -14,11 +15,39 @@ pub fn draw_status_bar(frame: &mut Frame, area: Rect, app: &App) {
         .as_deref()
         .unwrap_or("");
 
-    let paragraph = Paragraph::new(Line::from(Span::styled(
+    let mut spans = vec![Span::styled(
         format!(" {}", message),
         Style::default().fg(Color::Yellow).bg(Color::DarkGray),
-    )))
-    .style(Style::default().bg(Color::DarkGray));
+    )];
+
+    // Summarize the marked set of the active panel
+    let panel = app.active_panel();
+    let marked = panel.marked_count();
+    if marked > 0 {
+        spans.push(Span::styled(
+            format!("  [{} marked, {}]", marked, format_file_size(panel.marked_size())),
+            Style::default().fg(Color::LightRed).bg(Color::DarkGray),
+        ));
+    }
+
+    // Keep background transfers visible while the dialog is hidden
+    if let Some(job) = app.transfers.active() {
+        if !app.transfer_dialog_visible() {
+            spans.push(Span::styled(
+                format!(
+                    "  [{} {} {:.0}% +{} queued]",
+                    job.kind.verb(),
+                    job.name,
+                    job.progress.ratio() * 100.0,
+                    app.transfers.queued_count()
+                ),
+                Style::default().fg(Color::Cyan).bg(Color::DarkGray),
+            ));
+        }
+    }
+
+    let paragraph = Paragraph::new(Line::from(spans))
+        .style(Style::default().bg(Color::DarkGray));
 
     frame.render_widget(paragraph, area);
 }
@@ -41,7 +70,7 @@ pub fn draw_function_bar(frame: &mut Frame, area: Rect) {
         .flat_map(|(key, label)| {
             vec![
                 Span::styled(
-                    format!("{}", key),
+                    key.to_string(),
                     Style::default().bg(Color::Cyan).fg(Color::Black),
                 ),
                 Span::styled(
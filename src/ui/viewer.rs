@@ -0,0 +1,256 @@
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::filesystem::format_file_size;
+use crate::viewer::{ViewMode, Viewer, HEX_ROW_LEN};
+
+const TAB_WIDTH: usize = 8;
+
+/// Draw the F3 viewer over the whole screen
+pub fn draw_viewer(frame: &mut Frame, area: Rect, viewer: &mut Viewer) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" View: {} ", viewer.name))
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black).fg(Color::White));
+    let inner_area = block.inner(area);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner_area);
+    let content_area = chunks[0];
+    let height = content_area.height as usize;
+
+    let lines = match viewer.mode {
+        ViewMode::Text => text_view(viewer, height, content_area.width as usize),
+        ViewMode::Hex => hex_view(viewer, height),
+    };
+    match lines {
+        Ok(lines) => frame.render_widget(Paragraph::new(lines), content_area),
+        Err(e) => {
+            let error = Paragraph::new(format!("{:#}", e)).style(Style::default().fg(Color::Red));
+            frame.render_widget(error, content_area);
+        }
+    }
+
+    frame.render_widget(Paragraph::new(footer(viewer)), chunks[1]);
+}
+
+/// Lines of text from the top of the view, wrapped or scrolled sideways
+fn text_view(viewer: &mut Viewer, height: usize, width: usize) -> anyhow::Result<Vec<Line<'static>>> {
+    let width = width.max(1);
+    let mut rows = Vec::with_capacity(height);
+    let mut complete_lines = 0;
+
+    for (offset, bytes) in viewer.text_lines(height)? {
+        let cells = display_cells(offset, &bytes);
+
+        if viewer.wrap {
+            let pieces: Vec<&[(char, u64)]> = if cells.is_empty() {
+                vec![&[]]
+            } else {
+                cells.chunks(width).collect()
+            };
+            let fits = rows.len() + pieces.len() <= height;
+            for piece in pieces {
+                if rows.len() == height {
+                    break;
+                }
+                rows.push(styled_line(piece, viewer.highlight));
+            }
+            if fits {
+                complete_lines += 1;
+            }
+        } else {
+            let visible: Vec<(char, u64)> =
+                cells.into_iter().skip(viewer.left).take(width).collect();
+            rows.push(styled_line(&visible, viewer.highlight));
+            complete_lines += 1;
+        }
+
+        if rows.len() == height {
+            break;
+        }
+    }
+
+    // Paging moves by the lines that were shown in full
+    viewer.set_page_lines(if viewer.wrap { complete_lines } else { height });
+    Ok(rows)
+}
+
+/// Offset, hex bytes and ASCII columns, 16 bytes per row
+fn hex_view(viewer: &mut Viewer, height: usize) -> anyhow::Result<Vec<Line<'static>>> {
+    viewer.set_page_lines(height);
+    let highlight = viewer.highlight;
+    let is_highlighted =
+        |offset: u64| highlight.is_some_and(|(start, end)| offset >= start && offset < end);
+
+    let rows = viewer.hex_rows(height)?;
+    Ok(rows
+        .into_iter()
+        .map(|(offset, bytes)| {
+            let mut spans = vec![Span::styled(
+                format!("{:08x}  ", offset),
+                Style::default().fg(Color::Cyan),
+            )];
+
+            for i in 0..HEX_ROW_LEN as usize {
+                let separator = if i == 7 { "  " } else { " " };
+                match bytes.get(i) {
+                    Some(byte) => {
+                        let style = highlight_style(is_highlighted(offset + i as u64));
+                        spans.push(Span::styled(format!("{:02x}", byte), style));
+                    }
+                    None => spans.push(Span::raw("  ")),
+                }
+                spans.push(Span::raw(separator));
+            }
+
+            spans.push(Span::raw(" "));
+            for (i, byte) in bytes.iter().enumerate() {
+                let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                };
+                let style = highlight_style(is_highlighted(offset + i as u64));
+                spans.push(Span::styled(c.to_string(), style));
+            }
+
+            Line::from(spans)
+        })
+        .collect())
+}
+
+/// Characters to show for a line, each with the file offset it came from.
+/// Tabs are expanded and control characters shown as dots; lines that are
+/// not valid UTF-8 are shown byte by byte.
+fn display_cells(offset: u64, bytes: &[u8]) -> Vec<(char, u64)> {
+    let chars: Vec<(char, u64)> = match std::str::from_utf8(bytes) {
+        Ok(text) => text
+            .char_indices()
+            .map(|(i, c)| (c, offset + i as u64))
+            .collect(),
+        Err(_) => bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| {
+                let c = if b.is_ascii() { b as char } else { '.' };
+                (c, offset + i as u64)
+            })
+            .collect(),
+    };
+
+    let mut cells = Vec::with_capacity(chars.len());
+    for (c, at) in chars {
+        match c {
+            '\t' => {
+                let spaces = TAB_WIDTH - cells.len() % TAB_WIDTH;
+                cells.extend(std::iter::repeat_n((' ', at), spaces));
+            }
+            c if c.is_control() => cells.push(('.', at)),
+            c => cells.push((c, at)),
+        }
+    }
+    cells
+}
+
+/// A line with the search match picked out
+fn styled_line(cells: &[(char, u64)], highlight: Option<(u64, u64)>) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut text = String::new();
+    let mut current = false;
+
+    for &(c, at) in cells {
+        let highlighted = highlight.is_some_and(|(start, end)| at >= start && at < end);
+        if highlighted != current && !text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut text), highlight_style(current)));
+        }
+        current = highlighted;
+        text.push(c);
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, highlight_style(current)));
+    }
+
+    Line::from(spans)
+}
+
+fn highlight_style(highlighted: bool) -> Style {
+    if highlighted {
+        Style::default()
+            .bg(Color::Yellow)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+/// Position, mode flags and key hints
+fn footer(viewer: &Viewer) -> Line<'static> {
+    let percent = if viewer.size() == 0 {
+        100
+    } else {
+        viewer.top() * 100 / viewer.size()
+    };
+    let position = match (viewer.mode, viewer.top_line_number()) {
+        (ViewMode::Text, Some(line)) => format!("Line {}", line),
+        _ => format!("Offset 0x{:x}", viewer.top()),
+    };
+
+    let mut spans = vec![Span::styled(
+        format!(
+            " {}  {}%  {}{}{} ",
+            position,
+            percent,
+            format_file_size(viewer.size()),
+            if viewer.mode == ViewMode::Hex { "  [Hex]" } else { "" },
+            if viewer.wrap { "  [Wrap]" } else { "" },
+        ),
+        Style::default().fg(Color::Black).bg(Color::Cyan),
+    )];
+
+    if let Some(message) = &viewer.message {
+        spans.push(Span::styled(
+            format!(" {} ", message),
+            Style::default().fg(Color::Yellow),
+        ));
+    } else {
+        spans.push(Span::styled(
+            " F2 Wrap  F4 Hex  F5 Goto  F7 Search  n Next  Esc Close",
+            Style::default().fg(Color::Gray),
+        ));
+    }
+
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_cells_expands_tabs() {
+        let cells = display_cells(10, b"a\tb");
+        let text: String = cells.iter().map(|(c, _)| c).collect();
+        assert_eq!(text, "a       b");
+        assert_eq!(cells.last(), Some(&('b', 12)));
+    }
+
+    #[test]
+    fn test_display_cells_invalid_utf8() {
+        let cells = display_cells(0, &[b'o', 0xff, b'k', 0x07]);
+        let text: String = cells.iter().map(|(c, _)| c).collect();
+        assert_eq!(text, "o.k.");
+    }
+}
@@ -0,0 +1,571 @@
+//! Internal file viewer
+//!
+//! Files are read in fixed-size chunks on demand, so opening a multi-GB log,
+//! locally or over SFTP, only reads the part that is on screen.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, SeekFrom};
+use std::path::Path;
+
+use crate::transfer::{self, Endpoint, ReadSeek};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+const CACHED_CHUNKS: usize = 32;
+
+/// Longer lines are shown as several lines, so a file without newlines
+/// cannot stall scrolling
+const MAX_LINE_LEN: u64 = 4096;
+
+/// Bytes per row in hex mode
+pub const HEX_ROW_LEN: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    Text,
+    Hex,
+}
+
+/// Random access to a file through a small cache of chunks
+struct ChunkReader {
+    reader: Box<dyn ReadSeek>,
+    size: u64,
+    cache: HashMap<u64, Vec<u8>>,
+    order: VecDeque<u64>,
+}
+
+impl ChunkReader {
+    fn new(reader: Box<dyn ReadSeek>, size: u64) -> Self {
+        Self {
+            reader,
+            size,
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn chunk(&mut self, index: u64) -> Result<&[u8]> {
+        if !self.cache.contains_key(&index) {
+            let offset = index * CHUNK_SIZE;
+            self.reader
+                .seek(SeekFrom::Start(offset))
+                .context("Failed to seek in file")?;
+            let mut data = Vec::with_capacity(CHUNK_SIZE as usize);
+            (&mut self.reader)
+                .take(CHUNK_SIZE)
+                .read_to_end(&mut data)
+                .context("Failed to read file")?;
+
+            if self.order.len() >= CACHED_CHUNKS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.cache.remove(&oldest);
+                }
+            }
+            self.order.push_back(index);
+            self.cache.insert(index, data);
+        }
+        Ok(&self.cache[&index])
+    }
+
+    fn byte(&mut self, offset: u64) -> Result<Option<u8>> {
+        if offset >= self.size {
+            return Ok(None);
+        }
+        let chunk = self.chunk(offset / CHUNK_SIZE)?;
+        Ok(chunk.get((offset % CHUNK_SIZE) as usize).copied())
+    }
+
+    /// Up to `len` bytes starting at `offset`
+    fn read(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let end = (offset + len).min(self.size);
+        let mut data = Vec::with_capacity(end.saturating_sub(offset) as usize);
+        let mut pos = offset;
+        while pos < end {
+            let chunk = self.chunk(pos / CHUNK_SIZE)?;
+            let start = (pos % CHUNK_SIZE) as usize;
+            let take = ((end - pos) as usize).min(chunk.len().saturating_sub(start));
+            if take == 0 {
+                // The file shrank since it was opened
+                break;
+            }
+            data.extend_from_slice(&chunk[start..start + take]);
+            pos += take as u64;
+        }
+        Ok(data)
+    }
+}
+
+/// State of the F3 viewer
+pub struct Viewer {
+    pub name: String,
+    source: ChunkReader,
+    pub mode: ViewMode,
+    pub wrap: bool,
+    /// Offset of the first line (text) or row (hex) on screen
+    top: u64,
+    /// Line number of `top`, while it is known without rescanning the file
+    top_line: Option<u64>,
+    /// Horizontal scroll in characters when not wrapping
+    pub left: usize,
+    /// Lines or rows that fit on screen, set while drawing
+    page_lines: usize,
+    last_search: Option<String>,
+    /// Byte range of the last search match
+    pub highlight: Option<(u64, u64)>,
+    pub message: Option<String>,
+}
+
+impl Viewer {
+    /// Open a file on either side; binary files start in hex mode
+    pub fn open(endpoint: &Endpoint, path: &Path) -> Result<Self> {
+        let size = transfer::file_size(endpoint, path)
+            .ok_or_else(|| anyhow!("Not a regular file: {}", path.display()))?;
+        let reader = transfer::open_for_reading(endpoint, path)?;
+        Self::from_reader(path.display().to_string(), reader, size)
+    }
+
+    fn from_reader(name: String, reader: Box<dyn ReadSeek>, size: u64) -> Result<Self> {
+        let mut source = ChunkReader::new(reader, size);
+        let is_binary = size > 0 && source.chunk(0)?.contains(&0);
+
+        Ok(Self {
+            name,
+            source,
+            mode: if is_binary { ViewMode::Hex } else { ViewMode::Text },
+            wrap: false,
+            top: 0,
+            top_line: Some(0),
+            left: 0,
+            page_lines: 1,
+            last_search: None,
+            highlight: None,
+            message: None,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.source.size
+    }
+
+    pub fn top(&self) -> u64 {
+        self.top
+    }
+
+    /// 1-based number of the first line on screen, if known
+    pub fn top_line_number(&self) -> Option<u64> {
+        self.top_line.map(|line| line + 1)
+    }
+
+    pub fn last_search(&self) -> Option<&str> {
+        self.last_search.as_deref()
+    }
+
+    pub fn set_page_lines(&mut self, lines: usize) {
+        self.page_lines = lines.max(1);
+    }
+
+    /// Lines from the top of the screen, without line terminators
+    pub fn text_lines(&mut self, count: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        let mut lines = Vec::with_capacity(count);
+        let mut offset = self.top;
+        while lines.len() < count && offset < self.source.size {
+            let next = self.next_line_start(offset)?;
+            let mut line = self.source.read(offset, next - offset)?;
+            while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+                line.pop();
+            }
+            lines.push((offset, line));
+            offset = next;
+        }
+        Ok(lines)
+    }
+
+    /// Rows of `HEX_ROW_LEN` bytes from the top of the screen
+    pub fn hex_rows(&mut self, count: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        let mut rows = Vec::with_capacity(count);
+        let mut offset = self.top;
+        while rows.len() < count && offset < self.source.size {
+            rows.push((offset, self.source.read(offset, HEX_ROW_LEN)?));
+            offset += HEX_ROW_LEN;
+        }
+        Ok(rows)
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        let result = self.try_scroll_down(lines);
+        self.report(result);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        let result = self.try_scroll_up(lines);
+        self.report(result);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page_lines);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page_lines);
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.left = self.left.saturating_sub(8);
+    }
+
+    pub fn scroll_right(&mut self) {
+        if !self.wrap && self.mode == ViewMode::Text {
+            self.left += 8;
+        }
+    }
+
+    pub fn move_to_first(&mut self) {
+        self.top = 0;
+        self.top_line = Some(0);
+        self.left = 0;
+    }
+
+    /// Show the last page
+    pub fn move_to_last(&mut self) {
+        self.top_line = None;
+        match self.mode {
+            ViewMode::Hex => {
+                self.top = self.source.size.saturating_sub(1) / HEX_ROW_LEN * HEX_ROW_LEN;
+                self.scroll_up(self.page_lines - 1);
+            }
+            ViewMode::Text => {
+                // Scrolling up from the very end lands on the last line first
+                self.top = self.source.size;
+                self.scroll_up(self.page_lines);
+            }
+        }
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.left = 0;
+    }
+
+    pub fn toggle_hex(&mut self) {
+        let result = match self.mode {
+            ViewMode::Text => {
+                self.mode = ViewMode::Hex;
+                self.top = self.top / HEX_ROW_LEN * HEX_ROW_LEN;
+                self.top_line = None;
+                Ok(())
+            }
+            ViewMode::Hex => {
+                self.mode = ViewMode::Text;
+                self.line_start_of(self.top).map(|start| {
+                    self.top = start;
+                    self.top_line = (start == 0).then_some(0);
+                })
+            }
+        };
+        self.report(result);
+    }
+
+    /// Jump to a 1-based line number, or to a byte offset in hex mode
+    /// (decimal or 0x-prefixed hex)
+    pub fn goto(&mut self, target: &str) {
+        let result = match self.mode {
+            ViewMode::Text => target
+                .parse::<u64>()
+                .map_err(|_| anyhow!("Invalid line number: {}", target))
+                .and_then(|line| self.goto_line(line.max(1))),
+            ViewMode::Hex => parse_offset(target).map(|offset| {
+                let offset = offset.min(self.source.size.saturating_sub(1));
+                self.top = offset / HEX_ROW_LEN * HEX_ROW_LEN;
+            }),
+        };
+        self.report(result);
+    }
+
+    /// Search forward for `pattern` (ignoring ASCII case) and scroll to it
+    pub fn search(&mut self, pattern: &str) {
+        self.last_search = Some(pattern.to_string());
+        // Start from the top of the screen so a visible match is found first
+        self.highlight = None;
+        self.search_next();
+    }
+
+    /// Find the next match of the last search
+    pub fn search_next(&mut self) {
+        let pattern = match &self.last_search {
+            Some(pattern) if !pattern.is_empty() => pattern.clone(),
+            _ => return,
+        };
+        let from = match self.highlight {
+            Some((start, _)) => start + 1,
+            None => self.top,
+        };
+
+        let result = self.find(pattern.as_bytes(), from).and_then(|found| match found {
+            Some(offset) => {
+                self.highlight = Some((offset, offset + pattern.len() as u64));
+                self.show_offset(offset)
+            }
+            None => {
+                self.message = Some(format!("'{}' not found", pattern));
+                Ok(())
+            }
+        });
+        self.report(result);
+    }
+
+    fn report(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.message = Some(format!("{:#}", e));
+        }
+    }
+
+    fn try_scroll_down(&mut self, lines: usize) -> Result<()> {
+        for _ in 0..lines {
+            let next = match self.mode {
+                ViewMode::Hex => self.top + HEX_ROW_LEN,
+                ViewMode::Text => self.next_line_start(self.top)?,
+            };
+            if next >= self.source.size {
+                break;
+            }
+            if self.mode == ViewMode::Text && self.ends_line(next)? {
+                self.top_line = self.top_line.map(|line| line + 1);
+            }
+            self.top = next;
+        }
+        Ok(())
+    }
+
+    fn try_scroll_up(&mut self, lines: usize) -> Result<()> {
+        for _ in 0..lines {
+            if self.top == 0 {
+                break;
+            }
+            match self.mode {
+                ViewMode::Hex => self.top = self.top.saturating_sub(HEX_ROW_LEN),
+                ViewMode::Text => {
+                    if self.ends_line(self.top)? {
+                        self.top_line = self.top_line.map(|line| line.saturating_sub(1));
+                    }
+                    self.top = self.line_start_of(self.top - 1)?;
+                }
+            }
+        }
+        if self.top == 0 {
+            self.top_line = Some(0);
+        }
+        Ok(())
+    }
+
+    fn goto_line(&mut self, line: u64) -> Result<()> {
+        // Count newlines chunk by chunk rather than line by line
+        let mut remaining = line - 1;
+        let mut offset = 0;
+        while remaining > 0 && offset < self.source.size {
+            let chunk = self.source.chunk(offset / CHUNK_SIZE)?;
+            if chunk.is_empty() {
+                break;
+            }
+            let mut consumed = chunk.len() as u64;
+            for (i, &byte) in chunk.iter().enumerate() {
+                if byte == b'\n' {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        consumed = i as u64 + 1;
+                        break;
+                    }
+                }
+            }
+            offset += consumed;
+        }
+
+        if offset >= self.source.size && remaining > 0 {
+            self.message = Some(format!("File has only {} lines", line - 1 - remaining));
+            self.move_to_last();
+            return Ok(());
+        }
+        self.top = offset;
+        self.top_line = Some(line - 1);
+        Ok(())
+    }
+
+    /// Offset of the first occurrence of `pattern` at or after `from`
+    fn find(&mut self, pattern: &[u8], from: u64) -> Result<Option<u64>> {
+        let overlap = pattern.len() as u64 - 1;
+        let mut offset = from;
+        while offset < self.source.size {
+            // Read a little past the chunk so matches across chunk borders are seen
+            let window = self.source.read(offset, CHUNK_SIZE + overlap)?;
+            if window.len() < pattern.len() {
+                break;
+            }
+            if let Some(i) = window
+                .windows(pattern.len())
+                .position(|w| w.eq_ignore_ascii_case(pattern))
+            {
+                return Ok(Some(offset + i as u64));
+            }
+            offset += CHUNK_SIZE;
+        }
+        Ok(None)
+    }
+
+    /// Scroll so `offset` is on screen, leaving the view alone if it already is
+    fn show_offset(&mut self, offset: u64) -> Result<()> {
+        let page_end = match self.mode {
+            ViewMode::Hex => self.top + HEX_ROW_LEN * self.page_lines as u64,
+            ViewMode::Text => {
+                let lines = self.text_lines(self.page_lines)?;
+                lines.last().map_or(self.top, |(start, line)| start + line.len() as u64)
+            }
+        };
+        if offset >= self.top && offset < page_end {
+            return Ok(());
+        }
+
+        match self.mode {
+            ViewMode::Hex => self.top = offset / HEX_ROW_LEN * HEX_ROW_LEN,
+            ViewMode::Text => {
+                self.top = self.line_start_of(offset)?;
+                self.top_line = (self.top == 0).then_some(0);
+            }
+        }
+        Ok(())
+    }
+
+    /// True if the line before `offset` was ended by a newline rather than
+    /// split for being too long
+    fn ends_line(&mut self, offset: u64) -> Result<bool> {
+        Ok(offset > 0 && self.source.byte(offset - 1)? == Some(b'\n'))
+    }
+
+    /// Start of the line after the one starting at `offset`
+    fn next_line_start(&mut self, offset: u64) -> Result<u64> {
+        let limit = (offset + MAX_LINE_LEN).min(self.source.size);
+        let data = self.source.read(offset, limit - offset)?;
+        Ok(match data.iter().position(|&b| b == b'\n') {
+            Some(i) => offset + i as u64 + 1,
+            None => limit,
+        })
+    }
+
+    /// Start of the line containing `offset`
+    fn line_start_of(&mut self, offset: u64) -> Result<u64> {
+        let limit = offset.saturating_sub(MAX_LINE_LEN);
+        let mut pos = offset;
+        while pos > limit {
+            if self.source.byte(pos - 1)? == Some(b'\n') {
+                return Ok(pos);
+            }
+            pos -= 1;
+        }
+        Ok(pos)
+    }
+}
+
+/// Parse a decimal or 0x-prefixed hexadecimal offset
+pub fn parse_offset(text: &str) -> Result<u64> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| anyhow!("Invalid offset: {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn viewer_for(data: Vec<u8>) -> Viewer {
+        let size = data.len() as u64;
+        Viewer::from_reader("test".to_string(), Box::new(Cursor::new(data)), size).unwrap()
+    }
+
+    fn numbered_lines(count: usize) -> Vec<u8> {
+        (1..=count).map(|i| format!("line {}\n", i)).collect::<String>().into_bytes()
+    }
+
+    fn first_line(viewer: &mut Viewer) -> String {
+        let lines = viewer.text_lines(1).unwrap();
+        String::from_utf8(lines[0].1.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_scrolling_text() {
+        let mut viewer = viewer_for(numbered_lines(100));
+        viewer.set_page_lines(10);
+
+        viewer.scroll_down(3);
+        assert_eq!(first_line(&mut viewer), "line 4");
+        assert_eq!(viewer.top_line_number(), Some(4));
+
+        viewer.page_down();
+        assert_eq!(first_line(&mut viewer), "line 14");
+
+        viewer.scroll_up(2);
+        assert_eq!(first_line(&mut viewer), "line 12");
+        assert_eq!(viewer.top_line_number(), Some(12));
+
+        viewer.move_to_last();
+        assert_eq!(first_line(&mut viewer), "line 91");
+
+        viewer.move_to_first();
+        assert_eq!(first_line(&mut viewer), "line 1");
+    }
+
+    #[test]
+    fn test_goto_line_across_chunks() {
+        // Large enough to span several chunks
+        let mut viewer = viewer_for(numbered_lines(50_000));
+
+        viewer.goto("40000");
+        assert_eq!(first_line(&mut viewer), "line 40000");
+        assert_eq!(viewer.top_line_number(), Some(40000));
+
+        viewer.goto("999999");
+        assert!(viewer.message.as_deref().unwrap().contains("50000 lines"));
+    }
+
+    #[test]
+    fn test_search_finds_next_match() {
+        let mut viewer = viewer_for(numbered_lines(50_000));
+        viewer.set_page_lines(10);
+
+        viewer.search("LINE 3000");
+        let (start, _) = viewer.highlight.unwrap();
+        assert_eq!(first_line(&mut viewer), "line 3000");
+        assert_eq!(viewer.source.read(start, 9).unwrap(), b"line 3000");
+
+        viewer.search_next();
+        assert_eq!(first_line(&mut viewer), "line 30000");
+
+        viewer.search("no such text");
+        assert_eq!(viewer.message.as_deref(), Some("'no such text' not found"));
+    }
+
+    #[test]
+    fn test_binary_file_opens_in_hex_mode() {
+        let mut viewer = viewer_for((0u8..=255).collect());
+        assert_eq!(viewer.mode, ViewMode::Hex);
+
+        let rows = viewer.hex_rows(2).unwrap();
+        assert_eq!(rows[1].0, 16);
+        assert_eq!(rows[1].1, (16u8..32).collect::<Vec<_>>());
+
+        viewer.goto("0x20");
+        assert_eq!(viewer.top(), 32);
+    }
+
+    #[test]
+    fn test_long_lines_are_split() {
+        let mut data = vec![b'x'; MAX_LINE_LEN as usize * 2 + 10];
+        data.extend_from_slice(b"\nend\n");
+        let mut viewer = viewer_for(data);
+
+        let lines = viewer.text_lines(4).unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].1.len(), MAX_LINE_LEN as usize);
+        assert_eq!(lines[3].1, b"end");
+    }
+}
@@ -0,0 +1,1353 @@
+//! VT100/xterm emulation for the embedded shells
+//!
+//! Shell output is fed in as it arrives and updates a grid of styled cells,
+//! so drawing only has to read the current screen.
+
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::VecDeque;
+use unicode_width::UnicodeWidthChar;
+
+/// Lines kept by default once they scroll off the top of the main screen
+pub const DEFAULT_SCROLLBACK: usize = 1000;
+
+const TAB_WIDTH: usize = 8;
+
+/// Longest OSC string kept; the rest is dropped
+const MAX_OSC_LEN: usize = 4096;
+
+/// Marks the cell covered by the right half of a wide character
+const WIDE_CONTINUATION: char = '\0';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub c: char,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            c: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+impl Cell {
+    /// Whether this cell only holds the second half of a wide character
+    pub fn is_continuation(&self) -> bool {
+        self.c == WIDE_CONTINUATION
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub cells: Vec<Cell>,
+    /// The line was wrapped and continues on the next row
+    pub wrapped: bool,
+}
+
+impl Row {
+    fn new(cols: usize, blank: Cell) -> Self {
+        Self {
+            cells: vec![blank; cols],
+            wrapped: false,
+        }
+    }
+
+    /// The row's characters without trailing blanks
+    pub fn text(&self) -> String {
+        let text: String = self
+            .cells
+            .iter()
+            .filter(|cell| !cell.is_continuation())
+            .map(|cell| cell.c)
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    fn resize(&mut self, cols: usize, blank: Cell) {
+        self.cells.resize(cols, blank);
+        if let Some(last) = self.cells.last_mut() {
+            // Do not leave half of a wide character at the edge
+            if UnicodeWidthChar::width(last.c) == Some(2) {
+                *last = blank;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Ascii,
+    /// DEC special graphics, used for line drawing
+    Graphics,
+}
+
+/// Cursor state saved by DECSC and restored by DECRC
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    pen: Style,
+    origin_mode: bool,
+    charsets: [Charset; 2],
+    active_charset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// ESC followed by an intermediate byte, e.g. `ESC (`
+    EscapeIntermediate(u8),
+    Csi,
+    Osc,
+    OscEscape,
+    /// DCS, SOS, PM and APC strings, which are skipped
+    IgnoredString,
+    IgnoredStringEscape,
+}
+
+/// The terminal state of one shell
+pub struct Screen {
+    rows: usize,
+    cols: usize,
+    /// The screen being shown
+    grid: Vec<Row>,
+    /// The main screen while the alternate one is shown, and vice versa
+    other_grid: Vec<Row>,
+    alternate_screen: bool,
+    scrollback: VecDeque<Row>,
+    scrollback_limit: usize,
+    /// Lines dropped from the front of the scrollback so far, which keeps
+    /// absolute line numbers stable
+    dropped_lines: u64,
+
+    cursor_row: usize,
+    cursor_col: usize,
+    /// The cursor is past the last column; the next character wraps
+    pending_wrap: bool,
+    pen: Style,
+    saved_cursor: Option<SavedCursor>,
+    /// Saved when switching to the alternate screen with mode 1049
+    alternate_saved_cursor: Option<SavedCursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    tab_stops: Vec<bool>,
+    charsets: [Charset; 2],
+    active_charset: usize,
+    last_char: Option<char>,
+
+    autowrap: bool,
+    origin_mode: bool,
+    insert_mode: bool,
+    cursor_visible: bool,
+    application_cursor: bool,
+    bracketed_paste: bool,
+
+    title: Option<String>,
+    /// Working directory reported by the shell (OSC 7)
+    cwd: Option<String>,
+    /// Set when the shell reports its directory, cleared when input is sent
+    at_prompt: bool,
+    /// Replies to queries, to be written back to the shell
+    responses: Vec<u8>,
+
+    state: State,
+    params: Vec<Vec<u16>>,
+    private_marker: Option<u8>,
+    intermediate: Option<u8>,
+    osc: Vec<u8>,
+    utf8: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        Self {
+            rows,
+            cols,
+            grid: vec![Row::new(cols, Cell::default()); rows],
+            other_grid: vec![Row::new(cols, Cell::default()); rows],
+            alternate_screen: false,
+            scrollback: VecDeque::new(),
+            scrollback_limit: DEFAULT_SCROLLBACK,
+            dropped_lines: 0,
+            cursor_row: 0,
+            cursor_col: 0,
+            pending_wrap: false,
+            pen: Style::default(),
+            saved_cursor: None,
+            alternate_saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            tab_stops: default_tab_stops(cols),
+            charsets: [Charset::Ascii; 2],
+            active_charset: 0,
+            last_char: None,
+            autowrap: true,
+            origin_mode: false,
+            insert_mode: false,
+            cursor_visible: true,
+            application_cursor: false,
+            bracketed_paste: false,
+            title: None,
+            cwd: None,
+            at_prompt: false,
+            responses: Vec::new(),
+            state: State::Ground,
+            params: Vec::new(),
+            private_marker: None,
+            intermediate: None,
+            osc: Vec::new(),
+            utf8: Vec::new(),
+        }
+    }
+
+    /// The rows currently on screen
+    pub fn rows(&self) -> &[Row] {
+        &self.grid
+    }
+
+    /// Number of the oldest line still kept. Lines are numbered from the
+    /// start of the session through the scrollback and on to the screen.
+    pub fn first_line(&self) -> u64 {
+        self.dropped_lines
+    }
+
+    /// One past the number of the last screen row
+    pub fn end_line(&self) -> u64 {
+        self.dropped_lines + (self.scrollback.len() + self.rows) as u64
+    }
+
+    /// A line of the scrollback or the screen by number
+    pub fn line(&self, number: u64) -> Option<&Row> {
+        let index = number.checked_sub(self.dropped_lines)? as usize;
+        match index.checked_sub(self.scrollback.len()) {
+            Some(row) => self.grid.get(row),
+            None => self.scrollback.get(index),
+        }
+    }
+
+    /// How many lines to keep once they scroll off the screen
+    pub fn set_scrollback_limit(&mut self, limit: usize) {
+        self.scrollback_limit = limit;
+        self.trim_scrollback();
+    }
+
+    /// Rows and columns
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    /// Whether cursor keys should send application sequences (DECCKM)
+    pub fn application_cursor(&self) -> bool {
+        self.application_cursor
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The shell's working directory, if it reports one
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    /// Whether the shell is waiting at a prompt with nothing typed. Only
+    /// known for shells that report their directory before each prompt.
+    pub fn at_prompt(&self) -> bool {
+        self.at_prompt
+    }
+
+    /// Record that input was sent, so the shell is no longer idle
+    pub fn input_sent(&mut self) {
+        self.at_prompt = false;
+    }
+
+    /// Take the replies to terminal queries that should go back to the shell
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Change the screen size. Lines pushed off the top of the main screen
+    /// go to the scrollback so the cursor line stays visible.
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        if (rows, cols) == (self.rows, self.cols) {
+            return;
+        }
+
+        let blank = Cell::default();
+        for row in self.grid.iter_mut().chain(self.other_grid.iter_mut()) {
+            row.resize(cols, blank);
+        }
+
+        // Drop rows below the cursor first, then scroll the top away
+        while self.grid.len() > rows && self.grid.len() > self.cursor_row + 1 {
+            self.grid.pop();
+        }
+        while self.grid.len() > rows {
+            let row = self.grid.remove(0);
+            if !self.alternate_screen {
+                self.push_scrollback(row);
+            }
+            self.cursor_row = self.cursor_row.saturating_sub(1);
+        }
+        self.grid.resize(rows, Row::new(cols, blank));
+        self.other_grid.resize(rows, Row::new(cols, blank));
+
+        self.rows = rows;
+        self.cols = cols;
+        self.cursor_row = self.cursor_row.min(rows - 1);
+        self.cursor_col = self.cursor_col.min(cols - 1);
+        self.pending_wrap = false;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.tab_stops = default_tab_stops(cols);
+    }
+
+    /// Feed output from the shell
+    pub fn process(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.advance(byte);
+        }
+    }
+
+    fn advance(&mut self, byte: u8) {
+        match self.state {
+            State::Osc => {
+                match byte {
+                    0x07 => self.finish_osc(),
+                    0x1b => self.state = State::OscEscape,
+                    _ if self.osc.len() < MAX_OSC_LEN => self.osc.push(byte),
+                    _ => {}
+                }
+                return;
+            }
+            State::OscEscape => {
+                self.finish_osc();
+                if byte != b'\\' {
+                    self.state = State::Escape;
+                    self.advance(byte);
+                }
+                return;
+            }
+            State::IgnoredString => {
+                match byte {
+                    0x07 => self.state = State::Ground,
+                    0x1b => self.state = State::IgnoredStringEscape,
+                    _ => {}
+                }
+                return;
+            }
+            State::IgnoredStringEscape => {
+                self.state = State::Ground;
+                if byte != b'\\' {
+                    self.state = State::Escape;
+                    self.advance(byte);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        if !self.utf8.is_empty() || (byte >= 0x80 && self.state == State::Ground) {
+            self.advance_utf8(byte);
+            return;
+        }
+
+        match byte {
+            0x1b => {
+                self.state = State::Escape;
+                self.intermediate = None;
+            }
+            // CAN and SUB abort a sequence
+            0x18 | 0x1a => self.state = State::Ground,
+            0x00..=0x1f => self.execute(byte),
+            0x7f => {}
+            _ => match self.state {
+                State::Ground => self.print(byte as char),
+                State::Escape => self.escape(byte),
+                State::EscapeIntermediate(intermediate) => {
+                    self.state = State::Ground;
+                    self.escape_with_intermediate(intermediate, byte);
+                }
+                State::Csi => self.csi_byte(byte),
+                _ => {}
+            },
+        }
+    }
+
+    /// Collect a multi-byte UTF-8 character, printing U+FFFD for bad input
+    fn advance_utf8(&mut self, byte: u8) {
+        if self.utf8.is_empty() {
+            if byte & 0b1100_0000 == 0b1000_0000 || byte >= 0xf8 {
+                self.print(char::REPLACEMENT_CHARACTER);
+            } else {
+                self.utf8.push(byte);
+            }
+            return;
+        }
+
+        if byte & 0b1100_0000 != 0b1000_0000 {
+            // The sequence was cut short
+            self.utf8.clear();
+            self.print(char::REPLACEMENT_CHARACTER);
+            self.advance(byte);
+            return;
+        }
+
+        self.utf8.push(byte);
+        let expected = match self.utf8[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        if self.utf8.len() == expected {
+            let c = std::str::from_utf8(&self.utf8)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.utf8.clear();
+            self.print(c);
+        }
+    }
+
+    /// C0 control characters
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x08 => {
+                self.cursor_col = self.cursor_col.saturating_sub(1);
+                self.pending_wrap = false;
+            }
+            0x09 => self.tab_forward(1),
+            0x0a..=0x0c => self.linefeed(),
+            0x0d => {
+                self.cursor_col = 0;
+                self.pending_wrap = false;
+            }
+            0x0e => self.active_charset = 1,
+            0x0f => self.active_charset = 0,
+            _ => {}
+        }
+    }
+
+    fn escape(&mut self, byte: u8) {
+        self.state = State::Ground;
+        match byte {
+            b'[' => {
+                self.state = State::Csi;
+                self.params.clear();
+                self.private_marker = None;
+                self.intermediate = None;
+            }
+            b']' => {
+                self.state = State::Osc;
+                self.osc.clear();
+            }
+            b'P' | b'X' | b'^' | b'_' => self.state = State::IgnoredString,
+            0x20..=0x2f => self.state = State::EscapeIntermediate(byte),
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor_col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'H' => self.tab_stops[self.cursor_col] = true,
+            b'c' => self.reset(),
+            // Keypad modes and string terminators need no action
+            _ => {}
+        }
+    }
+
+    fn escape_with_intermediate(&mut self, intermediate: u8, byte: u8) {
+        match (intermediate, byte) {
+            (b'(', b'0') => self.charsets[0] = Charset::Graphics,
+            (b'(', _) => self.charsets[0] = Charset::Ascii,
+            (b')', b'0') => self.charsets[1] = Charset::Graphics,
+            (b')', _) => self.charsets[1] = Charset::Ascii,
+            (b'#', b'8') => {
+                // DECALN: fill the screen with E
+                for row in &mut self.grid {
+                    for cell in &mut row.cells {
+                        *cell = Cell {
+                            c: 'E',
+                            style: Style::default(),
+                        };
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_byte(&mut self, byte: u8) {
+        match byte {
+            b'0'..=b'9' => {
+                if self.params.is_empty() {
+                    self.params.push(vec![0]);
+                }
+                if let Some(value) = self.params.last_mut().and_then(|p| p.last_mut()) {
+                    *value = value.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                }
+            }
+            b';' => {
+                if self.params.is_empty() {
+                    self.params.push(vec![0]);
+                }
+                self.params.push(vec![0]);
+            }
+            b':' => {
+                if self.params.is_empty() {
+                    self.params.push(vec![0]);
+                }
+                if let Some(param) = self.params.last_mut() {
+                    param.push(0);
+                }
+            }
+            b'<'..=b'?' if self.params.is_empty() => self.private_marker = Some(byte),
+            0x20..=0x2f => self.intermediate = Some(byte),
+            0x40..=0x7e => {
+                self.state = State::Ground;
+                self.csi_dispatch(byte);
+            }
+            _ => {}
+        }
+    }
+
+    /// The nth parameter, or `default` if it is missing or zero
+    fn param(&self, index: usize, default: usize) -> usize {
+        match self.params.get(index).and_then(|p| p.first()) {
+            Some(&value) if value > 0 => value as usize,
+            _ => default,
+        }
+    }
+
+    fn csi_dispatch(&mut self, byte: u8) {
+        if self.intermediate.is_some() {
+            // Cursor style, soft reset and the like
+            if self.intermediate == Some(b'!') && byte == b'p' {
+                self.soft_reset();
+            }
+            return;
+        }
+
+        match (self.private_marker, byte) {
+            (Some(b'?'), b'h') => self.set_private_modes(true),
+            (Some(b'?'), b'l') => self.set_private_modes(false),
+            (Some(b'>'), b'c') => self.responses.extend_from_slice(b"\x1b[>0;10;1c"),
+            (Some(_), _) => {}
+            (None, _) => self.csi_standard(byte),
+        }
+    }
+
+    fn csi_standard(&mut self, byte: u8) {
+        let n = self.param(0, 1);
+        match byte {
+            b'@' => self.insert_chars(n),
+            b'A' => {
+                let top = if self.cursor_row >= self.scroll_top { self.scroll_top } else { 0 };
+                self.cursor_row = self.cursor_row.saturating_sub(n).max(top);
+                self.pending_wrap = false;
+            }
+            b'B' | b'e' => {
+                let bottom = if self.cursor_row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    self.rows - 1
+                };
+                self.cursor_row = (self.cursor_row + n).min(bottom);
+                self.pending_wrap = false;
+            }
+            b'C' | b'a' => {
+                self.cursor_col = (self.cursor_col + n).min(self.cols - 1);
+                self.pending_wrap = false;
+            }
+            b'D' => {
+                self.cursor_col = self.cursor_col.saturating_sub(n);
+                self.pending_wrap = false;
+            }
+            b'E' => {
+                self.csi_standard(b'B');
+                self.cursor_col = 0;
+            }
+            b'F' => {
+                self.csi_standard(b'A');
+                self.cursor_col = 0;
+            }
+            b'G' | b'`' => {
+                self.cursor_col = (n - 1).min(self.cols - 1);
+                self.pending_wrap = false;
+            }
+            b'H' | b'f' => {
+                let row = self.param(0, 1) - 1;
+                let col = self.param(1, 1) - 1;
+                self.move_to(row, col);
+            }
+            b'I' => self.tab_forward(n),
+            b'J' => self.erase_display(self.param(0, 0)),
+            b'K' => self.erase_line(self.param(0, 0)),
+            b'L' => self.insert_lines(n),
+            b'M' => self.delete_lines(n),
+            b'P' => self.delete_chars(n),
+            b'S' => self.scroll_up(n),
+            b'T' => self.scroll_down(n),
+            b'X' => {
+                let blank = self.blank_cell();
+                let end = (self.cursor_col + n).min(self.cols);
+                let row = &mut self.grid[self.cursor_row];
+                row.cells[self.cursor_col..end].fill(blank);
+            }
+            b'Z' => {
+                for _ in 0..n {
+                    self.cursor_col = (0..self.cursor_col)
+                        .rev()
+                        .find(|&col| self.tab_stops[col])
+                        .unwrap_or(0);
+                }
+                self.pending_wrap = false;
+            }
+            b'b' => {
+                if let Some(c) = self.last_char {
+                    for _ in 0..n.min(self.rows * self.cols) {
+                        self.print(c);
+                    }
+                }
+            }
+            b'c' => self.responses.extend_from_slice(b"\x1b[?1;2c"),
+            b'd' => {
+                let col = self.cursor_col;
+                self.move_to(n - 1, col);
+            }
+            b'g' => match self.param(0, 0) {
+                0 => self.tab_stops[self.cursor_col] = false,
+                3 => self.tab_stops.fill(false),
+                _ => {}
+            },
+            b'h' | b'l' => {
+                for index in 0..self.params.len() {
+                    if self.param(index, 0) == 4 {
+                        self.insert_mode = byte == b'h';
+                    }
+                }
+            }
+            b'm' => self.select_graphic_rendition(),
+            b'n' => match self.param(0, 0) {
+                5 => self.responses.extend_from_slice(b"\x1b[0n"),
+                6 => {
+                    let row = if self.origin_mode {
+                        self.cursor_row - self.scroll_top
+                    } else {
+                        self.cursor_row
+                    };
+                    let report = format!("\x1b[{};{}R", row + 1, self.cursor_col + 1);
+                    self.responses.extend_from_slice(report.as_bytes());
+                }
+                _ => {}
+            },
+            b'r' => {
+                let top = self.param(0, 1) - 1;
+                let bottom = self.param(1, self.rows).min(self.rows) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn set_private_modes(&mut self, enable: bool) {
+        for index in 0..self.params.len() {
+            match self.param(index, 0) {
+                1 => self.application_cursor = enable,
+                6 => {
+                    self.origin_mode = enable;
+                    self.move_to(0, 0);
+                }
+                7 => self.autowrap = enable,
+                25 => self.cursor_visible = enable,
+                47 | 1047 => self.switch_screen(enable),
+                1048 if enable => self.save_cursor(),
+                1048 => self.restore_cursor(),
+                1049 if enable => {
+                    self.alternate_saved_cursor = Some(self.cursor_state());
+                    self.switch_screen(true);
+                }
+                1049 => {
+                    self.switch_screen(false);
+                    if let Some(saved) = self.alternate_saved_cursor.take() {
+                        self.apply_cursor_state(saved);
+                    }
+                }
+                2004 => self.bracketed_paste = enable,
+                _ => {}
+            }
+        }
+    }
+
+    /// Show the alternate screen, cleared, or go back to the main one
+    fn switch_screen(&mut self, alternate: bool) {
+        if alternate == self.alternate_screen {
+            return;
+        }
+        std::mem::swap(&mut self.grid, &mut self.other_grid);
+        self.alternate_screen = alternate;
+        if alternate {
+            let blank = Cell::default();
+            for row in &mut self.grid {
+                *row = Row::new(self.cols, blank);
+            }
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.pending_wrap = false;
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        if self.params.is_empty() {
+            self.pen = Style::default();
+            return;
+        }
+
+        let params = std::mem::take(&mut self.params);
+        let mut i = 0;
+        while i < params.len() {
+            let param = &params[i];
+            match param[0] {
+                0 => self.pen = Style::default(),
+                1 => self.pen = self.pen.add_modifier(Modifier::BOLD),
+                2 => self.pen = self.pen.add_modifier(Modifier::DIM),
+                3 => self.pen = self.pen.add_modifier(Modifier::ITALIC),
+                4 => {
+                    // 4:0 turns underline off
+                    if param.get(1) == Some(&0) {
+                        self.pen = self.pen.remove_modifier(Modifier::UNDERLINED);
+                    } else {
+                        self.pen = self.pen.add_modifier(Modifier::UNDERLINED);
+                    }
+                }
+                5 => self.pen = self.pen.add_modifier(Modifier::SLOW_BLINK),
+                6 => self.pen = self.pen.add_modifier(Modifier::RAPID_BLINK),
+                7 => self.pen = self.pen.add_modifier(Modifier::REVERSED),
+                8 => self.pen = self.pen.add_modifier(Modifier::HIDDEN),
+                9 => self.pen = self.pen.add_modifier(Modifier::CROSSED_OUT),
+                21 | 22 => self.pen = self.pen.remove_modifier(Modifier::BOLD | Modifier::DIM),
+                23 => self.pen = self.pen.remove_modifier(Modifier::ITALIC),
+                24 => self.pen = self.pen.remove_modifier(Modifier::UNDERLINED),
+                25 => {
+                    self.pen = self
+                        .pen
+                        .remove_modifier(Modifier::SLOW_BLINK | Modifier::RAPID_BLINK)
+                }
+                27 => self.pen = self.pen.remove_modifier(Modifier::REVERSED),
+                28 => self.pen = self.pen.remove_modifier(Modifier::HIDDEN),
+                29 => self.pen = self.pen.remove_modifier(Modifier::CROSSED_OUT),
+                code @ 30..=37 => self.pen.fg = Some(ansi_color(code - 30)),
+                39 => self.pen.fg = None,
+                code @ 40..=47 => self.pen.bg = Some(ansi_color(code - 40)),
+                49 => self.pen.bg = None,
+                code @ 90..=97 => self.pen.fg = Some(ansi_color(code - 90 + 8)),
+                code @ 100..=107 => self.pen.bg = Some(ansi_color(code - 100 + 8)),
+                code @ (38 | 48 | 58) => {
+                    // Either 38:5:n / 38:2:[id:]r:g:b, or the same spread over ';'
+                    let (color, used) = if param.len() > 1 {
+                        (extended_color(&param[1..]), 0)
+                    } else {
+                        let rest: Vec<u16> = params[i + 1..].iter().map(|p| p[0]).collect();
+                        extended_color_params(&rest)
+                    };
+                    i += used;
+                    match code {
+                        38 => self.pen.fg = color.or(self.pen.fg),
+                        48 => self.pen.bg = color.or(self.pen.bg),
+                        _ => self.pen.underline_color = color.or(self.pen.underline_color),
+                    }
+                }
+                59 => self.pen.underline_color = None,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn finish_osc(&mut self) {
+        self.state = State::Ground;
+        let osc = String::from_utf8_lossy(&self.osc).to_string();
+        let (code, value) = osc.split_once(';').unwrap_or((osc.as_str(), ""));
+        match code {
+            "0" | "2" => self.title = Some(value.to_string()),
+            "7" => {
+                if let Some(path) = file_url_path(value) {
+                    self.cwd = Some(path);
+                    self.at_prompt = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let c = match self.charsets[self.active_charset] {
+            Charset::Graphics => dec_graphics(c),
+            Charset::Ascii => c,
+        };
+        let width = match UnicodeWidthChar::width(c) {
+            Some(0) | None => return,
+            Some(width) => width.min(2),
+        };
+
+        if self.pending_wrap && self.autowrap {
+            self.grid[self.cursor_row].wrapped = true;
+            self.cursor_col = 0;
+            self.linefeed();
+        }
+        self.pending_wrap = false;
+
+        if width == 2 && self.cursor_col + 1 >= self.cols {
+            if !self.autowrap || self.cols < 2 {
+                return;
+            }
+            // No room for both halves on this row
+            let blank = self.blank_cell();
+            self.grid[self.cursor_row].cells[self.cursor_col] = blank;
+            self.grid[self.cursor_row].wrapped = true;
+            self.cursor_col = 0;
+            self.linefeed();
+        }
+
+        if self.insert_mode {
+            self.insert_chars(width);
+        }
+
+        let style = self.pen;
+        let col = self.cursor_col;
+        self.clear_wide_char_at(col);
+        self.grid[self.cursor_row].cells[col] = Cell { c, style };
+        if width == 2 {
+            self.clear_wide_char_at(col + 1);
+            self.grid[self.cursor_row].cells[col + 1] = Cell {
+                c: WIDE_CONTINUATION,
+                style,
+            };
+        }
+        self.last_char = Some(c);
+
+        if col + width >= self.cols {
+            self.cursor_col = self.cols - 1;
+            self.pending_wrap = self.autowrap;
+        } else {
+            self.cursor_col = col + width;
+        }
+    }
+
+    /// Blank out the other half of a wide character about to be overwritten
+    fn clear_wide_char_at(&mut self, col: usize) {
+        let blank = self.blank_cell();
+        let cells = &mut self.grid[self.cursor_row].cells;
+        if cells[col].is_continuation() && col > 0 {
+            cells[col - 1] = blank;
+        }
+        if cells.get(col + 1).is_some_and(Cell::is_continuation) {
+            cells[col + 1] = blank;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor_row + 1 < self.rows {
+            self.cursor_row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.cursor_row = self.cursor_row.saturating_sub(1);
+        }
+    }
+
+    /// Scroll the scroll region up, saving lines that leave the main screen
+    fn scroll_up(&mut self, n: usize) {
+        let blank = self.blank_cell();
+        for _ in 0..n.min(self.scroll_bottom - self.scroll_top + 1) {
+            let row = self.grid.remove(self.scroll_top);
+            if self.scroll_top == 0 && !self.alternate_screen {
+                self.push_scrollback(row);
+            }
+            self.grid.insert(self.scroll_bottom, Row::new(self.cols, blank));
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let blank = self.blank_cell();
+        for _ in 0..n.min(self.scroll_bottom - self.scroll_top + 1) {
+            self.grid.remove(self.scroll_bottom);
+            self.grid.insert(self.scroll_top, Row::new(self.cols, blank));
+        }
+    }
+
+    fn push_scrollback(&mut self, row: Row) {
+        self.scrollback.push_back(row);
+        self.trim_scrollback();
+    }
+
+    fn trim_scrollback(&mut self) {
+        while self.scrollback.len() > self.scrollback_limit {
+            self.scrollback.pop_front();
+            self.dropped_lines += 1;
+        }
+    }
+
+    fn clear_scrollback(&mut self) {
+        self.dropped_lines += self.scrollback.len() as u64;
+        self.scrollback.clear();
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom {
+            return;
+        }
+        let blank = self.blank_cell();
+        for _ in 0..n.min(self.scroll_bottom - self.cursor_row + 1) {
+            self.grid.remove(self.scroll_bottom);
+            self.grid.insert(self.cursor_row, Row::new(self.cols, blank));
+        }
+        self.cursor_col = 0;
+        self.pending_wrap = false;
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom {
+            return;
+        }
+        let blank = self.blank_cell();
+        for _ in 0..n.min(self.scroll_bottom - self.cursor_row + 1) {
+            self.grid.remove(self.cursor_row);
+            self.grid.insert(self.scroll_bottom, Row::new(self.cols, blank));
+        }
+        self.cursor_col = 0;
+        self.pending_wrap = false;
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let blank = self.blank_cell();
+        let col = self.cursor_col;
+        let cells = &mut self.grid[self.cursor_row].cells;
+        let n = n.min(cells.len() - col);
+        cells[col..].rotate_right(n);
+        cells[col..col + n].fill(blank);
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let blank = self.blank_cell();
+        let col = self.cursor_col;
+        let cells = &mut self.grid[self.cursor_row].cells;
+        let n = n.min(cells.len() - col);
+        cells[col..].rotate_left(n);
+        let len = cells.len();
+        cells[len - n..].fill(blank);
+        self.pending_wrap = false;
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let blank = self.blank_cell();
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for row in &mut self.grid[self.cursor_row + 1..] {
+                    *row = Row::new(self.cols, blank);
+                }
+            }
+            1 => {
+                self.erase_line(1);
+                for row in &mut self.grid[..self.cursor_row] {
+                    *row = Row::new(self.cols, blank);
+                }
+            }
+            2 => {
+                for row in &mut self.grid {
+                    *row = Row::new(self.cols, blank);
+                }
+            }
+            3 => self.clear_scrollback(),
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let blank = self.blank_cell();
+        let col = self.cursor_col;
+        let row = &mut self.grid[self.cursor_row];
+        match mode {
+            0 => {
+                row.cells[col..].fill(blank);
+                row.wrapped = false;
+            }
+            1 => row.cells[..=col].fill(blank),
+            2 => *row = Row::new(self.cols, blank),
+            _ => {}
+        }
+    }
+
+    fn tab_forward(&mut self, n: usize) {
+        for _ in 0..n {
+            self.cursor_col = (self.cursor_col + 1..self.cols)
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(self.cols - 1);
+        }
+        self.pending_wrap = false;
+    }
+
+    /// Move to a position, relative to the scroll region in origin mode
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor_row = if self.origin_mode {
+            (self.scroll_top + row).min(self.scroll_bottom)
+        } else {
+            row.min(self.rows - 1)
+        };
+        self.cursor_col = col.min(self.cols - 1);
+        self.pending_wrap = false;
+    }
+
+    fn cursor_state(&self) -> SavedCursor {
+        SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            pen: self.pen,
+            origin_mode: self.origin_mode,
+            charsets: self.charsets,
+            active_charset: self.active_charset,
+        }
+    }
+
+    fn apply_cursor_state(&mut self, saved: SavedCursor) {
+        self.cursor_row = saved.row.min(self.rows - 1);
+        self.cursor_col = saved.col.min(self.cols - 1);
+        self.pen = saved.pen;
+        self.origin_mode = saved.origin_mode;
+        self.charsets = saved.charsets;
+        self.active_charset = saved.active_charset;
+        self.pending_wrap = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor_state());
+    }
+
+    fn restore_cursor(&mut self) {
+        match self.saved_cursor {
+            Some(saved) => self.apply_cursor_state(saved),
+            None => self.move_to(0, 0),
+        }
+    }
+
+    /// DECSTR: reset modes but keep the screen contents
+    fn soft_reset(&mut self) {
+        self.pen = Style::default();
+        self.autowrap = true;
+        self.origin_mode = false;
+        self.insert_mode = false;
+        self.cursor_visible = true;
+        self.application_cursor = false;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.charsets = [Charset::Ascii; 2];
+        self.active_charset = 0;
+        self.saved_cursor = None;
+    }
+
+    /// RIS: back to the power-on state, keeping the scrollback
+    fn reset(&mut self) {
+        let mut fresh = Self::new(self.rows, self.cols);
+        fresh.scrollback = std::mem::take(&mut self.scrollback);
+        fresh.scrollback_limit = self.scrollback_limit;
+        fresh.dropped_lines = self.dropped_lines;
+        fresh.responses = std::mem::take(&mut self.responses);
+        *self = fresh;
+    }
+
+    /// Erased cells keep the current background color, like xterm
+    fn blank_cell(&self) -> Cell {
+        Cell {
+            c: ' ',
+            style: Style {
+                bg: self.pen.bg,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+fn default_tab_stops(cols: usize) -> Vec<bool> {
+    (0..cols).map(|col| col > 0 && col % TAB_WIDTH == 0).collect()
+}
+
+/// One of the 16 standard colors
+fn ansi_color(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+/// Parse `5:n` or `2:[colorspace:]r:g:b` from colon-separated subparameters
+fn extended_color(sub: &[u16]) -> Option<Color> {
+    match sub {
+        [5, n, ..] => Some(Color::Indexed((*n).min(255) as u8)),
+        [2, _, r, g, b, ..] => Some(rgb(*r, *g, *b)),
+        [2, r, g, b] => Some(rgb(*r, *g, *b)),
+        _ => None,
+    }
+}
+
+/// Parse `5;n` or `2;r;g;b` following 38/48, returning how many params were used
+fn extended_color_params(rest: &[u16]) -> (Option<Color>, usize) {
+    match rest {
+        [5, n, ..] => (Some(Color::Indexed((*n).min(255) as u8)), 2),
+        [2, r, g, b, ..] => (Some(rgb(*r, *g, *b)), 4),
+        _ => (None, rest.len()),
+    }
+}
+
+fn rgb(r: u16, g: u16, b: u16) -> Color {
+    Color::Rgb(r.min(255) as u8, g.min(255) as u8, b.min(255) as u8)
+}
+
+/// Map a character in the DEC special graphics set to its Unicode equivalent
+fn dec_graphics(c: char) -> char {
+    match c {
+        '`' => '◆',
+        'a' => '▒',
+        'f' => '°',
+        'g' => '±',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        c => c,
+    }
+}
+
+/// Path of a `file://host/path` URL, with percent escapes decoded
+fn file_url_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3).and_then(|hex| {
+            u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+        });
+        match escape {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_text(screen: &Screen) -> Vec<String> {
+        screen.rows().iter().map(Row::text).collect()
+    }
+
+    fn history_text(screen: &Screen) -> Vec<String> {
+        let screen_start = screen.end_line() - screen.rows().len() as u64;
+        (screen.first_line()..screen_start)
+            .map(|line| screen.line(line).unwrap().text())
+            .collect()
+    }
+
+    #[test]
+    fn test_wraps_at_right_margin() {
+        let mut screen = Screen::new(3, 5);
+        screen.process(b"hello world");
+
+        assert_eq!(screen_text(&screen), ["hello", " worl", "d"]);
+        assert!(screen.rows()[0].wrapped);
+        assert_eq!(screen.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn test_cursor_movement_and_erase() {
+        let mut screen = Screen::new(3, 10);
+        screen.process(b"one\r\ntwo\r\nthree");
+        screen.process(b"\x1b[2;2H\x1b[K\x1b[1;1H\x1b[2P");
+
+        assert_eq!(screen_text(&screen), ["e", "t", "three"]);
+        assert_eq!(screen.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn test_scroll_region_keeps_other_lines() {
+        let mut screen = Screen::new(4, 10);
+        screen.process(b"header\x1b[2;3r\x1b[2;1Ha\r\nb\r\nc\x1b[4;1Hfooter");
+
+        assert_eq!(screen_text(&screen), ["header", "b", "c", "footer"]);
+        // Lines scrolled out of a partial region are not history
+        assert!(history_text(&screen).is_empty());
+    }
+
+    #[test]
+    fn test_scrollback_collects_lines() {
+        let mut screen = Screen::new(2, 10);
+        screen.process(b"1\r\n2\r\n3\r\n4");
+
+        assert_eq!(history_text(&screen), ["1", "2"]);
+        assert_eq!(screen_text(&screen), ["3", "4"]);
+
+        // Line numbers stay the same when old lines are dropped
+        assert_eq!(screen.line(1).unwrap().text(), "2");
+        screen.set_scrollback_limit(1);
+        assert_eq!(screen.first_line(), 1);
+        assert_eq!(screen.line(1).unwrap().text(), "2");
+        assert!(screen.line(0).is_none());
+    }
+
+    #[test]
+    fn test_alternate_screen_restores_main_screen() {
+        let mut screen = Screen::new(2, 10);
+        screen.process(b"$ less\x1b[?1049h\x1b[Hpage\x1b[?25l");
+        assert!(screen.alternate_screen());
+        assert!(!screen.cursor_visible());
+        assert_eq!(screen_text(&screen), ["page", ""]);
+
+        screen.process(b"\x1b[?1049l");
+        assert!(!screen.alternate_screen());
+        assert_eq!(screen_text(&screen), ["$ less", ""]);
+        assert_eq!(screen.cursor(), (0, 6));
+    }
+
+    #[test]
+    fn test_sgr_colors() {
+        let mut screen = Screen::new(1, 10);
+        screen.process(b"\x1b[1;38;5;208ma\x1b[48;2;1;2;3mb\x1b[38:2::9:8:7mc\x1b[0md");
+
+        let cells = &screen.rows()[0].cells;
+        assert_eq!(cells[0].style.fg, Some(Color::Indexed(208)));
+        assert!(cells[0].style.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(cells[1].style.bg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(cells[2].style.fg, Some(Color::Rgb(9, 8, 7)));
+        assert_eq!(cells[3].style, Style::default());
+    }
+
+    #[test]
+    fn test_utf8_split_across_reads() {
+        let mut screen = Screen::new(1, 10);
+        let text = "né日".as_bytes();
+        screen.process(&text[..2]);
+        screen.process(&text[2..4]);
+        screen.process(&text[4..]);
+
+        assert_eq!(screen_text(&screen), ["né日"]);
+        // The wide character takes two columns
+        assert_eq!(screen.cursor(), (0, 4));
+    }
+
+    #[test]
+    fn test_queries_are_answered() {
+        let mut screen = Screen::new(5, 10);
+        screen.process(b"\x1b[3;4H\x1b[6n\x1b[c");
+        assert_eq!(screen.take_responses(), b"\x1b[3;4R\x1b[?1;2c");
+        assert!(screen.take_responses().is_empty());
+    }
+
+    #[test]
+    fn test_working_directory_report() {
+        let mut screen = Screen::new(4, 20);
+        assert_eq!(screen.cwd(), None);
+
+        screen.process(b"\x1b]7;file://host/home/me/My%20Files\x07$ ");
+        assert_eq!(screen.cwd(), Some("/home/me/My Files"));
+        assert!(screen.at_prompt());
+
+        screen.input_sent();
+        assert!(!screen.at_prompt());
+        screen.process(b"\x1b]7;not a url\x1b\\");
+        assert_eq!(screen.cwd(), Some("/home/me/My Files"));
+        assert!(!screen.at_prompt());
+    }
+
+    #[test]
+    fn test_resize_keeps_cursor_line() {
+        let mut screen = Screen::new(4, 10);
+        screen.process(b"a\r\nb\r\nc\r\nprompt");
+        screen.resize(2, 8);
+
+        assert_eq!(screen_text(&screen), ["c", "prompt"]);
+        assert_eq!(screen.cursor(), (1, 6));
+        assert_eq!(history_text(&screen), ["a", "b"]);
+    }
+}